@@ -1,7 +1,14 @@
+use std::{collections::HashMap, sync::Arc};
+
 use kira::sound::static_sound::{StaticSoundData, StaticSoundSettings};
 use sfml::SfBox;
 use sfml::graphics::{Texture, Font};
 
+use crate::{
+    tile_registry::{TileRegistry, TILE_REGISTRY_PATH},
+    weapon::{WeaponRegistry, WEAPON_REGISTRY_PATH},
+};
+
 #[derive(Clone)]
 pub struct Assets {
     pub terrain_texture: SfBox<Texture>,
@@ -10,8 +17,13 @@ pub struct Assets {
 
     pub font: SfBox<Font>,
 
-    pub handgun_cock: StaticSoundData,
-    pub handgun_fire: StaticSoundData,
+    // every sound a weapon refers to, by file name without the .wav
+    pub sounds: HashMap<String, StaticSoundData>,
+
+    pub weapons: Arc<WeaponRegistry>,
+    // only used on the main thread, for chunk vertices and tile lookups. in an Arc since the
+    // registry itself isn't Clone
+    pub tiles: Arc<TileRegistry>,
 }
 
 impl Assets {
@@ -23,8 +35,17 @@ impl Assets {
 
         let font = Font::from_file("res/default.ttf").unwrap();
 
-        let handgun_cock = StaticSoundData::from_file("res/sounds/handgun_cock.wav", StaticSoundSettings::default()).unwrap();
-        let handgun_fire = StaticSoundData::from_file("res/sounds/handgun_fire.wav", StaticSoundSettings::default()).unwrap();
+        let weapons = Arc::new(WeaponRegistry::load(WEAPON_REGISTRY_PATH).unwrap());
+
+        let mut sounds = HashMap::new();
+        for name in weapons.definitions().flat_map(|weapon| weapon.sounds()) {
+            if !sounds.contains_key(name) {
+                let sound = StaticSoundData::from_file(&format!("res/sounds/{}.wav", name), StaticSoundSettings::default()).unwrap();
+                sounds.insert(name.to_string(), sound);
+            }
+        }
+
+        let tiles = Arc::new(TileRegistry::load(TILE_REGISTRY_PATH).unwrap());
 
         Assets {
             terrain_texture,
@@ -33,8 +54,11 @@ impl Assets {
             
             font,
 
-            handgun_cock,
-            handgun_fire,
+            sounds,
+
+            weapons,
+
+            tiles,
         }
     }
 }
\ No newline at end of file
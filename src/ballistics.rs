@@ -0,0 +1,272 @@
+use rand::Rng;
+use sfml::{graphics::FloatRect, system::Vector2f};
+
+use crate::entities::entity::EntityType;
+
+// damage left at the very end of a shot's range, as a fraction of what it starts with
+const MIN_DAMAGE_FRACTION: f32 = 0.4;
+// how long tracers and impact marks stay on screen
+pub const TRACER_TICKS: u32 = 6;
+pub const IMPACT_TICKS: u32 = 30;
+
+// one bullet leaving a barrel. hitscan shots land the tick they're fired, the rest travel
+// speed pixels per tick
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Shot {
+    pub origin: Vector2f,
+    // unit vector, spread already applied
+    pub direction: Vector2f,
+    pub range: f32,
+    pub damage: f32,
+    // full damage up to here, falling off to the end of the range
+    pub falloff_start: f32,
+    pub speed: Option<f32>,
+}
+
+impl Shot {
+    pub fn damage_at(&self, distance: f32) -> f32 {
+        if distance <= self.falloff_start {
+            return self.damage;
+        }
+
+        let falloff = ((distance - self.falloff_start) / (self.range - self.falloff_start).max(1.0)).min(1.0);
+        self.damage * (1.0 - falloff * (1.0 - MIN_DAMAGE_FRACTION))
+    }
+}
+
+// where a ray stopped. target is whatever it hit, None for walls
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Hit {
+    pub target: Option<usize>,
+    pub position: Vector2f,
+    pub distance: f32,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ImpactKind {
+    FLESH = 0,
+    WALL = 1,
+}
+
+// a mark left where a bullet landed, fades out over IMPACT_TICKS
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Impact {
+    pub position: Vector2f,
+    pub kind: ImpactKind,
+    pub ticks_left: u32,
+}
+
+// the streak a hitscan shot leaves for a few ticks
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Tracer {
+    pub from: Vector2f,
+    pub to: Vector2f,
+    pub ticks_left: u32,
+}
+
+// a shot that takes its time getting there
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Projectile {
+    pub shot: Shot,
+    pub position: Vector2f,
+    pub travelled: f32,
+    // what fired it, it can't hit its own kind
+    pub shooter: EntityType,
+}
+
+impl Projectile {
+    pub fn new(shot: Shot, shooter: EntityType) -> Self {
+        Projectile { shot, position: shot.origin, travelled: 0.0, shooter }
+    }
+
+    // how far it goes this tick, never past the end of its range
+    pub fn step_length(&self) -> f32 {
+        self.shot.speed.unwrap_or(self.shot.range).min(self.shot.range - self.travelled)
+    }
+
+    pub fn is_spent(&self) -> bool {
+        self.travelled >= self.shot.range
+    }
+
+    pub fn advance(&mut self, distance: f32) {
+        self.position += self.shot.direction * distance;
+        self.travelled += distance;
+    }
+
+    // ends it where it hit something
+    pub fn stop_at(&mut self, distance: f32) {
+        self.advance(distance);
+        self.travelled = self.shot.range;
+    }
+
+    pub fn is_hitscan(&self) -> bool {
+        self.shot.speed.is_none()
+    }
+}
+
+// aim turned by a random angle of up to half the spread either way
+pub fn spread_direction(aim: Vector2f, spread_degrees: f32, rng: &mut impl Rng) -> Vector2f {
+    let angle = aim.y.atan2(aim.x);
+    let half_spread = (spread_degrees / 2.0).to_radians();
+    let offset = if half_spread > 0.0 { rng.gen_range(-half_spread..=half_spread) } else { 0.0 };
+
+    Vector2f::new((angle + offset).cos(), (angle + offset).sin())
+}
+
+// distance along the ray to where it enters the circle, 0 if it starts inside
+pub fn ray_circle(origin: Vector2f, direction: Vector2f, center: Vector2f, radius: f32) -> Option<f32> {
+    let to_center = center - origin;
+    let along = to_center.x * direction.x + to_center.y * direction.y;
+    let closest_sq = to_center.length_sq() - along * along;
+
+    if to_center.length_sq() <= radius * radius {
+        return Some(0.0);
+    }
+    if along < 0.0 || closest_sq > radius * radius {
+        return None;
+    }
+
+    Some(along - (radius * radius - closest_sq).sqrt())
+}
+
+// distance along the ray to where it enters the rect, 0 if it starts inside
+pub fn ray_box(origin: Vector2f, direction: Vector2f, rect: FloatRect) -> Option<f32> {
+    let mut entry: f32 = 0.0;
+    let mut exit = f32::INFINITY;
+
+    for (start, step, low, high) in [
+        (origin.x, direction.x, rect.left, rect.left + rect.width),
+        (origin.y, direction.y, rect.top, rect.top + rect.height),
+    ] {
+        if step == 0.0 {
+            if start < low || start > high {
+                return None;
+            }
+            continue;
+        }
+
+        let (near, far) = ((low - start) / step, (high - start) / step);
+        entry = entry.max(near.min(far));
+        exit = exit.min(near.max(far));
+    }
+
+    (entry <= exit).then_some(entry)
+}
+
+// the first thing a ray of that length runs into. targets are (index, position, radius), wall
+// is how far along the ray the first wall is, if there's one within reach
+pub fn cast(
+    origin: Vector2f,
+    direction: Vector2f,
+    length: f32,
+    targets: impl Iterator<Item = (usize, Vector2f, f32)>,
+    wall: Option<f32>,
+) -> Option<Hit> {
+    let mut nearest = wall
+        .filter(|distance| *distance <= length)
+        .map(|distance| Hit { target: None, position: origin + direction * distance, distance });
+
+    for (index, center, radius) in targets {
+        let Some(distance) = ray_circle(origin, direction, center, radius) else {
+            continue;
+        };
+
+        if distance <= length && !matches!(nearest, Some(hit) if hit.distance <= distance) {
+            nearest = Some(Hit { target: Some(index), position: origin + direction * distance, distance });
+        }
+    }
+
+    nearest
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    const ORIGIN: Vector2f = Vector2f::new(0.0, 0.0);
+    const RIGHT: Vector2f = Vector2f::new(1.0, 0.0);
+
+    fn shot(speed: Option<f32>) -> Shot {
+        Shot { origin: ORIGIN, direction: RIGHT, range: 500.0, damage: 40.0, falloff_start: 100.0, speed }
+    }
+
+    #[test]
+    fn rays_enter_circles_and_boxes_on_their_near_side() {
+        assert_eq!(ray_circle(ORIGIN, RIGHT, Vector2f::new(50.0, 0.0), 10.0), Some(40.0));
+        assert_eq!(ray_circle(ORIGIN, RIGHT, Vector2f::new(50.0, 11.0), 10.0), None);
+        assert_eq!(ray_circle(ORIGIN, RIGHT, Vector2f::new(-50.0, 0.0), 10.0), None);
+        assert_eq!(ray_circle(ORIGIN, RIGHT, Vector2f::new(5.0, 0.0), 10.0), Some(0.0));
+
+        let tile = FloatRect::new(64.0, -16.0, 32.0, 32.0);
+        assert_eq!(ray_box(ORIGIN, RIGHT, tile), Some(64.0));
+        assert_eq!(ray_box(ORIGIN, Vector2f::new(0.0, 1.0), tile), None);
+        assert_eq!(ray_box(ORIGIN, Vector2f::new(-1.0, 0.0), tile), None);
+
+        let diagonal = Vector2f::new(1.0, 1.0) / 2f32.sqrt();
+        let corner = ray_box(ORIGIN, diagonal, FloatRect::new(10.0, 10.0, 5.0, 5.0)).unwrap();
+        assert!((corner - 200f32.sqrt()).abs() < 0.001);
+    }
+
+    #[test]
+    fn casts_stop_at_whatever_comes_first() {
+        let zombies = [(3, Vector2f::new(200.0, 0.0), 10.0), (5, Vector2f::new(100.0, 2.0), 10.0), (6, Vector2f::new(50.0, 40.0), 10.0)];
+
+        let hit = cast(ORIGIN, RIGHT, 500.0, zombies.iter().copied(), None).unwrap();
+        assert_eq!(hit.target, Some(5));
+
+        // a wall in front of everyone soaks it up
+        let hit = cast(ORIGIN, RIGHT, 500.0, zombies.iter().copied(), Some(64.0)).unwrap();
+        assert_eq!((hit.target, hit.position), (None, Vector2f::new(64.0, 0.0)));
+
+        // nothing in range
+        assert_eq!(cast(ORIGIN, RIGHT, 80.0, zombies.iter().copied(), Some(300.0)), None);
+    }
+
+    #[test]
+    fn damage_falls_off_past_the_falloff_start() {
+        let shot = shot(None);
+
+        assert_eq!(shot.damage_at(0.0), 40.0);
+        assert_eq!(shot.damage_at(100.0), 40.0);
+        assert!(shot.damage_at(300.0) < 40.0);
+        assert!((shot.damage_at(500.0) - 40.0 * MIN_DAMAGE_FRACTION).abs() < 0.001);
+        assert!((shot.damage_at(900.0) - 40.0 * MIN_DAMAGE_FRACTION).abs() < 0.001);
+    }
+
+    #[test]
+    fn spread_stays_inside_its_cone() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let half_spread = 5f32.to_radians();
+
+        for _ in 0..200 {
+            let direction = spread_direction(Vector2f::new(0.0, -3.0), 10.0, &mut rng);
+            assert!((direction.length_sq() - 1.0).abs() < 0.001);
+            assert!(direction.y < 0.0 && direction.x.abs() <= half_spread.sin() + 0.001);
+        }
+
+        assert_eq!(spread_direction(Vector2f::new(2.0, 0.0), 0.0, &mut rng), RIGHT);
+    }
+
+    #[test]
+    fn projectiles_stop_at_the_end_of_their_range() {
+        let mut projectile = Projectile::new(shot(Some(120.0)), EntityType::PLAYER);
+
+        let mut steps = 0;
+        while !projectile.is_spent() {
+            let length = projectile.step_length();
+            projectile.advance(length);
+            steps += 1;
+        }
+
+        assert_eq!(steps, 5);
+        assert_eq!(projectile.position, Vector2f::new(500.0, 0.0));
+
+        let mut hitscan = Projectile::new(shot(None), EntityType::PLAYER);
+        assert_eq!(hitscan.step_length(), 500.0);
+        hitscan.stop_at(20.0);
+        assert!(hitscan.is_spent());
+        assert_eq!(hitscan.position, Vector2f::new(20.0, 0.0));
+    }
+}
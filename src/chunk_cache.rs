@@ -0,0 +1,127 @@
+use std::collections::{HashMap, VecDeque};
+
+use crate::map::Chunk;
+
+// 4 MiB worth of unloaded chunks, a bit under 800 of them including their vertex arrays
+pub const DEFAULT_CACHE_BUDGET: usize = 4 * 1024 * 1024;
+
+// least recently unloaded chunks get dropped first once the budget runs out.
+// chunks only ever go in when they're unloaded and come out when they're loaded again,
+// so insertion order is the same as use order
+pub struct ChunkCache {
+    pub budget: usize,
+    used: usize,
+    stamp: u64,
+
+    chunks: HashMap<(i32, i32), (Chunk, u64)>,
+    // oldest first. entries whose stamp doesn't match the map anymore are stale and skipped
+    order: VecDeque<((i32, i32), u64)>,
+}
+
+impl ChunkCache {
+    pub fn new(budget: usize) -> Self {
+        ChunkCache {
+            budget,
+            used: 0,
+            stamp: 0,
+
+            chunks: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    // caches the chunk and returns whatever had to be evicted to stay inside the budget
+    pub fn insert(&mut self, chunk: Chunk) -> Vec<Chunk> {
+        let position = chunk.chunk_position();
+
+        self.stamp += 1;
+        self.used += chunk.memory_size();
+        self.order.push_back((position, self.stamp));
+
+        if let Some((old_chunk, _)) = self.chunks.insert(position, (chunk, self.stamp)) {
+            self.used -= old_chunk.memory_size();
+        }
+
+        // chunks that were taken back out leave stale entries behind, clear them out every so often
+        if self.order.len() > self.chunks.len() * 2 + 64 {
+            let chunks = &self.chunks;
+            self.order.retain(|(position, stamp)| {
+                chunks.get(position).is_some_and(|(_, current)| current == stamp)
+            });
+        }
+
+        let mut evicted = Vec::new();
+
+        while self.used > self.budget {
+            let Some((oldest, stamp)) = self.order.pop_front() else { break };
+
+            if self.chunks.get(&oldest).is_some_and(|(_, current)| *current == stamp) {
+                let (chunk, _) = self.chunks.remove(&oldest).unwrap();
+                self.used -= chunk.memory_size();
+                evicted.push(chunk);
+            }
+        }
+
+        evicted
+    }
+
+    pub fn take(&mut self, position: (i32, i32)) -> Option<Chunk> {
+        let (chunk, _) = self.chunks.remove(&position)?;
+        self.used -= chunk.memory_size();
+
+        Some(chunk)
+    }
+
+    // empties the cache, handing back everything in it
+    pub fn drain(&mut self) -> Vec<Chunk> {
+        self.used = 0;
+        self.order.clear();
+        self.chunks.drain().map(|(_, (chunk, _))| chunk).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::{Chunk, CHUNK_SIZE_PIXELS};
+
+    use super::*;
+
+    fn chunk_at(chunk_x: i32, chunk_y: i32) -> Chunk {
+        Chunk::template(chunk_x * CHUNK_SIZE_PIXELS as i32, chunk_y * CHUNK_SIZE_PIXELS as i32)
+    }
+
+    #[test]
+    fn evicts_least_recently_inserted_first() {
+        let chunk_size = chunk_at(0, 0).memory_size();
+        let mut cache = ChunkCache::new(chunk_size * 2);
+
+        assert!(cache.insert(chunk_at(0, 0)).is_empty());
+        assert!(cache.insert(chunk_at(1, 0)).is_empty());
+
+        let evicted = cache.insert(chunk_at(2, 0));
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].chunk_position(), (0, 0));
+
+        assert!(cache.take((0, 0)).is_none());
+        assert!(cache.take((1, 0)).is_some());
+        assert!(cache.take((2, 0)).is_some());
+    }
+
+    #[test]
+    fn taken_chunks_are_not_evicted_later() {
+        let chunk_size = chunk_at(0, 0).memory_size();
+        let mut cache = ChunkCache::new(chunk_size * 2);
+
+        cache.insert(chunk_at(0, 0));
+        cache.insert(chunk_at(1, 0));
+        assert!(cache.take((0, 0)).is_some());
+
+        // re-inserting (0, 0) makes it the newest, so (1, 0) is the one that goes
+        cache.insert(chunk_at(0, 0));
+        let evicted = cache.insert(chunk_at(2, 0));
+
+        assert_eq!(evicted.len(), 1);
+        assert_eq!(evicted[0].chunk_position(), (1, 0));
+        assert!(cache.take((0, 0)).is_some());
+    }
+}
@@ -0,0 +1,303 @@
+use sfml::system::Vector2f;
+
+use crate::{entities::entity::{EntityType, HITBOX_SIZE}, map::TILE_SIZE};
+
+// keeps a box's right/bottom edge out of the next tile when it's lined up exactly on a border
+const EDGE: f32 = 0.001;
+// how far past touching entities start steering away from each other
+const SEPARATION_RANGE: f32 = 12.0;
+// pixels per tick two entities right on top of each other drift apart by
+const SEPARATION_STRENGTH: f32 = 0.3;
+
+// two entities touching this tick. first and second index into GameState::entities, which gets
+// reordered when rendering, so these only mean anything until the end of the tick
+#[derive(Clone, Copy, Debug)]
+pub struct Contact {
+    pub first: usize,
+    pub second: usize,
+    pub first_type: EntityType,
+    pub second_type: EntityType,
+    // points from second towards first
+    pub normal: Vector2f,
+    pub depth: f32,
+}
+
+// moves a box centred on position by movement, one axis at a time so hitting a wall on one
+// axis still lets it slide along the other. is_blocked is asked about world tiles.
+// tiles the box already overlaps are ignored, so anything stuck inside a wall can walk out
+pub fn move_and_slide(position: Vector2f, size: Vector2f, movement: Vector2f, is_blocked: impl Fn(i32, i32) -> bool) -> Vector2f {
+    let half = size / 2.0;
+
+    let x = move_axis(position.x, half.x, movement.x, |column| {
+        tile_span(position.y, half.y).any(|row| is_blocked(column, row))
+    });
+
+    let y = move_axis(position.y, half.y, movement.y, |row| {
+        tile_span(x, half.x).any(|column| is_blocked(column, row))
+    });
+
+    Vector2f::new(x, y)
+}
+
+// every tile a box centred on center with half size half covers along one axis
+fn tile_span(center: f32, half: f32) -> impl Iterator<Item = i32> {
+    tile_of(center - half)..=tile_of(center + half - EDGE)
+}
+
+fn tile_of(coordinate: f32) -> i32 {
+    (coordinate / TILE_SIZE as f32).floor() as i32
+}
+
+// walks the leading edge tile by tile up to where the move ends and stops at the first blocked
+// line, so fast movement can't skip over a thin wall
+fn move_axis(center: f32, half: f32, movement: f32, line_blocked: impl Fn(i32) -> bool) -> f32 {
+    let target = center + movement;
+    let tile_size = TILE_SIZE as f32;
+
+    if movement > 0.0 {
+        let current = tile_of(center + half - EDGE);
+        let last = tile_of(target + half - EDGE);
+
+        match (current + 1..=last).find(|line| line_blocked(*line)) {
+            Some(line) => line as f32 * tile_size - half,
+            None => target,
+        }
+    } else if movement < 0.0 {
+        let current = tile_of(center - half);
+        let last = tile_of(target - half);
+
+        match (last..current).rev().find(|line| line_blocked(*line)) {
+            Some(line) => (line + 1) as f32 * tile_size + half,
+            None => target,
+        }
+    } else {
+        center
+    }
+}
+
+// how far to push circle a so it stops overlapping circle b, None if they don't touch. two
+// circles on exactly the same point are split along fallback, which should differ per pair so a
+// stacked crowd doesn't all leave in the same direction
+pub fn circle_overlap(a: Vector2f, a_radius: f32, b: Vector2f, b_radius: f32, fallback: Vector2f) -> Option<(Vector2f, f32)> {
+    let delta = a - b;
+    let distance = delta.length_sq().sqrt();
+    let depth = a_radius + b_radius - distance;
+
+    if depth <= 0.0 {
+        return None;
+    }
+
+    let normal = if distance > 0.0 { delta / distance } else { fallback };
+    Some((normal, depth))
+}
+
+// zombies spread out instead of stacking on one point, and nothing walks through anything
+// else. bodies are (position, radius, type), nearby gives the index of every body within reach
+// of a point. returns how far to push each body and every pair that's touching. overlaps are
+// pushed apart completely, near misses drift apart slowly. the player doesn't get shoved
+// around, whatever it touches takes the whole push
+pub fn separation(bodies: &[(Vector2f, f32, EntityType)], nearby: impl Fn(Vector2f, f32) -> Vec<usize>) -> (Vec<Vector2f>, Vec<Contact>) {
+    let mut pushes = vec![Vector2f::new(0.0, 0.0); bodies.len()];
+    let mut contacts = Vec::new();
+
+    for (first, (a_position, a_radius, a_type)) in bodies.iter().copied().enumerate() {
+        // corpses are walked over
+        if a_type == EntityType::CORPSE {
+            continue;
+        }
+
+        // nothing is bigger than a hitbox, so this reaches everything close enough to steer
+        let mut neighbours = nearby(a_position, a_radius + HITBOX_SIZE / 2.0 + SEPARATION_RANGE);
+        // same order every tick so the pushes add up the same way
+        neighbours.sort();
+
+        for second in neighbours.into_iter().filter(|second| *second > first && bodies[*second].2 != EntityType::CORPSE) {
+            let (b_position, b_radius, b_type) = bodies[second];
+
+            // any direction works for entities on the same point, as long as each pair picks
+            // a different one
+            let angle = (first * 7 + second * 13) as f32;
+            let fallback = Vector2f::new(angle.cos(), angle.sin());
+
+            let (a_share, b_share) = match (a_type, b_type) {
+                (EntityType::PLAYER, _) => (0.0, 1.0),
+                (_, EntityType::PLAYER) => (1.0, 0.0),
+                _ => (0.5, 0.5),
+            };
+
+            let steering_range = SEPARATION_RANGE / 2.0;
+            if let Some((normal, closeness)) = circle_overlap(a_position, a_radius + steering_range, b_position, b_radius + steering_range, fallback) {
+                let strength = SEPARATION_STRENGTH * closeness / (a_radius + b_radius + SEPARATION_RANGE);
+                pushes[first] += normal * strength * a_share;
+                pushes[second] -= normal * strength * b_share;
+            }
+
+            if let Some((normal, depth)) = circle_overlap(a_position, a_radius, b_position, b_radius, fallback) {
+                pushes[first] += normal * depth * a_share;
+                pushes[second] -= normal * depth * b_share;
+
+                contacts.push(Contact {
+                    first,
+                    second,
+                    first_type: a_type,
+                    second_type: b_type,
+                    normal,
+                    depth,
+                });
+            }
+        }
+    }
+
+    (pushes, contacts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Vector2f = Vector2f::new(20.0, 20.0);
+
+    // a wall along tile column 3, and a single block at (-2, 0)
+    fn is_blocked(tile_x: i32, tile_y: i32) -> bool {
+        tile_x == 3 || (tile_x, tile_y) == (-2, 0)
+    }
+
+    #[test]
+    fn free_movement_is_untouched() {
+        let position = Vector2f::new(40.0, 40.0);
+        let movement = Vector2f::new(12.5, -30.0);
+
+        assert_eq!(move_and_slide(position, SIZE, movement, is_blocked), position + movement);
+        assert_eq!(move_and_slide(position, SIZE, Vector2f::new(0.0, 0.0), is_blocked), position);
+    }
+
+    #[test]
+    fn walls_stop_movement_flush_against_them() {
+        // the wall starts at x = 96, so the box's centre stops half its width before that
+        let moved = move_and_slide(Vector2f::new(70.0, 40.0), SIZE, Vector2f::new(20.0, 0.0), is_blocked);
+        assert_eq!(moved, Vector2f::new(86.0, 40.0));
+
+        // and stays there
+        let moved = move_and_slide(moved, SIZE, Vector2f::new(5.0, 0.0), is_blocked);
+        assert_eq!(moved, Vector2f::new(86.0, 40.0));
+    }
+
+    #[test]
+    fn fast_movement_does_not_tunnel() {
+        let moved = move_and_slide(Vector2f::new(40.0, 40.0), SIZE, Vector2f::new(500.0, 0.0), is_blocked);
+        assert_eq!(moved.x, 86.0);
+    }
+
+    #[test]
+    fn diagonal_movement_slides_along_walls() {
+        let moved = move_and_slide(Vector2f::new(86.0, 40.0), SIZE, Vector2f::new(3.0, 4.0), is_blocked);
+        assert_eq!(moved, Vector2f::new(86.0, 44.0));
+    }
+
+    #[test]
+    fn negative_coordinates_collide_too() {
+        // block (-2, 0) covers x -64..-32, y 0..32
+        let moved = move_and_slide(Vector2f::new(-10.0, 16.0), SIZE, Vector2f::new(-40.0, 0.0), is_blocked);
+        assert_eq!(moved, Vector2f::new(-22.0, 16.0));
+
+        // coming up from below it
+        let moved = move_and_slide(Vector2f::new(-48.0, 60.0), SIZE, Vector2f::new(0.0, -40.0), is_blocked);
+        assert_eq!(moved, Vector2f::new(-48.0, 42.0));
+
+        // a box exactly lined up with the row below doesn't touch it
+        let moved = move_and_slide(Vector2f::new(-10.0, 42.0), SIZE, Vector2f::new(-60.0, 0.0), is_blocked);
+        assert_eq!(moved, Vector2f::new(-70.0, 42.0));
+    }
+
+    #[test]
+    fn overlapping_circles_push_apart() {
+        let fallback = Vector2f::new(0.0, 1.0);
+
+        let (normal, depth) = circle_overlap(Vector2f::new(15.0, 0.0), 10.0, Vector2f::new(0.0, 0.0), 10.0, fallback).unwrap();
+        assert_eq!(normal, Vector2f::new(1.0, 0.0));
+        assert_eq!(depth, 5.0);
+
+        assert!(circle_overlap(Vector2f::new(20.0, 0.0), 10.0, Vector2f::new(0.0, 0.0), 10.0, fallback).is_none());
+
+        // stacked on the same point
+        let (normal, depth) = circle_overlap(Vector2f::new(3.0, 3.0), 10.0, Vector2f::new(3.0, 3.0), 10.0, fallback).unwrap();
+        assert_eq!(normal, fallback);
+        assert_eq!(depth, 20.0);
+    }
+
+    // every body within reach, what the spatial hash does in game
+    fn separate(bodies: &[(Vector2f, f32, EntityType)]) -> (Vec<Vector2f>, Vec<Contact>) {
+        separation(bodies, |position, reach| {
+            (0..bodies.len()).filter(|index| (bodies[*index].0 - position).length_sq() <= reach * reach).collect()
+        })
+    }
+
+    #[test]
+    fn overlapping_zombies_end_up_apart() {
+        let mut bodies = [
+            (Vector2f::new(0.0, 0.0), 10.0, EntityType::ZOMBIE),
+            (Vector2f::new(4.0, 0.0), 10.0, EntityType::ZOMBIE),
+            (Vector2f::new(4.0, 0.0), 10.0, EntityType::ZOMBIE),
+        ];
+
+        // one tick at a time, the way the game applies them
+        for _ in 0..10 {
+            let (pushes, _) = separate(&bodies);
+            for (body, push) in bodies.iter_mut().zip(pushes) {
+                body.0 += push;
+            }
+        }
+
+        for first in 0..bodies.len() {
+            for second in first + 1..bodies.len() {
+                let distance = (bodies[first].0 - bodies[second].0).length_sq().sqrt();
+                assert!(distance >= 20.0, "{} and {} are {} apart", first, second, distance);
+            }
+        }
+        assert!(separate(&bodies).1.is_empty());
+    }
+
+    #[test]
+    fn the_player_is_not_shoved() {
+        let bodies = [
+            (Vector2f::new(0.0, 0.0), 10.0, EntityType::PLAYER),
+            (Vector2f::new(15.0, 0.0), 10.0, EntityType::ZOMBIE),
+        ];
+
+        let (pushes, _) = separate(&bodies);
+        assert_eq!(pushes[0], Vector2f::new(0.0, 0.0));
+        // the zombie takes the whole overlap, plus a little steering
+        assert!(pushes[1].x >= 5.0 && pushes[1].y == 0.0);
+    }
+
+    #[test]
+    fn touching_pairs_make_one_contact_each() {
+        let bodies = [
+            (Vector2f::new(0.0, 0.0), 10.0, EntityType::ZOMBIE),
+            (Vector2f::new(0.0, 0.0), 0.0, EntityType::CORPSE),
+            (Vector2f::new(15.0, 0.0), 10.0, EntityType::PLAYER),
+            (Vector2f::new(24.0, 0.0), 10.0, EntityType::ZOMBIE),
+            // close enough to be steered away, not to touch
+            (Vector2f::new(0.0, 25.0), 10.0, EntityType::ZOMBIE),
+        ];
+
+        let (pushes, contacts) = separate(&bodies);
+
+        let mut pairs: Vec<(usize, usize)> = contacts.iter().map(|contact| (contact.first, contact.second)).collect();
+        pairs.sort();
+        assert_eq!(pairs, vec![(0, 2), (2, 3)]);
+
+        let contact = contacts.iter().find(|contact| contact.first == 0).unwrap();
+        assert_eq!((contact.first_type, contact.second_type), (EntityType::ZOMBIE, EntityType::PLAYER));
+        assert_eq!((contact.normal, contact.depth), (Vector2f::new(-1.0, 0.0), 5.0));
+
+        assert_eq!(pushes[1], Vector2f::new(0.0, 0.0));
+        assert!(pushes[4].y > 0.0);
+    }
+
+    #[test]
+    fn boxes_stuck_in_walls_can_leave() {
+        let moved = move_and_slide(Vector2f::new(100.0, 40.0), SIZE, Vector2f::new(30.0, 0.0), is_blocked);
+        assert_eq!(moved, Vector2f::new(130.0, 40.0));
+    }
+}
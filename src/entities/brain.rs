@@ -0,0 +1,427 @@
+use std::ops::RangeInclusive;
+
+use rand::Rng;
+use sfml::system::Vector2f;
+
+// close enough to where it was heading to count as there
+const ARRIVE_DISTANCE: f32 = 8.0;
+// anything this close gets noticed whichever way it's facing
+const PERIPHERAL_RANGE: f32 = 40.0;
+// how quickly the player gets noticed at the edge of sight range, compared to right up close
+const FAR_DETECTION: f32 = 0.2;
+// crouching players are this much harder to notice
+const CROUCH_VISIBILITY: f32 = 0.4;
+// awareness drains this many times slower than it fills at point blank
+const AWARENESS_DECAY: f32 = 3.0;
+// half aware is enough to go and have a look
+const SUSPICIOUS: f32 = 0.5;
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Behavior {
+    // standing around
+    IDLE = 0,
+    // strolling to a random spot nearby
+    WANDERING = 1,
+    // heading for somewhere it heard or last saw something, giving up after a while
+    INVESTIGATING = 2,
+    // can see the player and is going for them
+    CHASING = 3,
+    // close enough to hit the player
+    ATTACKING = 4,
+    // just lost sight of the player but still follows them for a bit
+    LOSING_TRACK = 5,
+}
+
+// how long each behavior lasts and how far things reach, tuned per zombie kind. times are in ticks
+#[derive(Clone, PartialEq, Debug)]
+pub struct BehaviorTimings {
+    pub idle_ticks: RangeInclusive<u32>,
+    pub wander_ticks: RangeInclusive<u32>,
+    pub wander_radius: f32,
+    pub investigate_ticks: u32,
+    pub lose_track_ticks: u32,
+    pub attack_cooldown_ticks: u32,
+    pub sight_range: f32,
+    pub attack_range: f32,
+    // full width of the vision cone in degrees
+    pub vision_angle: f32,
+    // ticks to notice a player standing right in front of it in full light
+    pub notice_ticks: u32,
+}
+
+// what a zombie knows about the world this tick
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Perception {
+    pub player: Vector2f,
+    // nothing blocks the view between the zombie and the player. still needs to be in range
+    pub line_of_sight: bool,
+    // loudest noise it heard this tick
+    pub heard: Option<Vector2f>,
+    // how lit the player's spot is, 0 is pitch black and 1 is broad daylight
+    pub light: f32,
+    pub crouching: bool,
+}
+
+// a zombie's state machine. think runs once per tick, everything else just reads it
+#[derive(Clone, PartialEq, Debug)]
+pub struct Brain {
+    pub behavior: Behavior,
+    // ticks left in the current behavior, for the ones that time out
+    pub timer: u32,
+    // last place the player was seen or a noise was heard
+    pub last_known: Option<Vector2f>,
+    pub wander_target: Option<Vector2f>,
+    pub attack_cooldown: u32,
+    // set on the tick an attack lands
+    pub attacked: bool,
+    // unit vector it's looking along, the middle of its vision cone
+    pub facing: Vector2f,
+    // how sure it is the player is there, chases at 1
+    pub awareness: f32,
+}
+
+impl Brain {
+    pub fn new() -> Self {
+        Brain {
+            behavior: Behavior::IDLE,
+            timer: 0,
+            last_known: None,
+            wander_target: None,
+            attack_cooldown: 0,
+            attacked: false,
+            facing: Vector2f::new(0.0, 1.0),
+            awareness: 0.0,
+        }
+    }
+
+    // where it's walking to, None while it stands still
+    pub fn goal(&self) -> Option<Vector2f> {
+        match self.behavior {
+            Behavior::IDLE | Behavior::ATTACKING => None,
+            Behavior::WANDERING => self.wander_target,
+            Behavior::INVESTIGATING | Behavior::CHASING | Behavior::LOSING_TRACK => self.last_known,
+        }
+    }
+
+    pub fn is_hunting(&self) -> bool {
+        matches!(self.behavior, Behavior::CHASING | Behavior::LOSING_TRACK)
+    }
+
+    // player is in range, in the vision cone or right next to it, and not behind a wall
+    pub fn can_see(&self, position: Vector2f, perception: &Perception, timings: &BehaviorTimings) -> bool {
+        let player_distance = (position - perception.player).length_sq().sqrt();
+
+        if !perception.line_of_sight || player_distance > timings.sight_range {
+            return false;
+        }
+        if player_distance <= PERIPHERAL_RANGE {
+            return true;
+        }
+
+        let direction = (perception.player - position) / player_distance;
+        let half_angle = (timings.vision_angle / 2.0).to_radians();
+        direction.x * self.facing.x + direction.y * self.facing.y >= half_angle.cos()
+    }
+
+    // awareness gained per tick while the player is in view
+    fn detection_rate(player_distance: f32, perception: &Perception, timings: &BehaviorTimings) -> f32 {
+        let closeness = 1.0 - (1.0 - FAR_DETECTION) * (player_distance / timings.sight_range).min(1.0);
+        let stance = if perception.crouching { CROUCH_VISIBILITY } else { 1.0 };
+
+        closeness * perception.light.clamp(0.0, 1.0) * stance / timings.notice_ticks.max(1) as f32
+    }
+
+    // bumping into the player gives them away however dark it is or however low they crouch
+    pub fn touched_player(&mut self) {
+        self.awareness = 1.0;
+    }
+
+    fn switch(&mut self, behavior: Behavior, timer: u32) {
+        self.behavior = behavior;
+        self.timer = timer;
+    }
+
+    pub fn think(&mut self, position: Vector2f, perception: &Perception, timings: &BehaviorTimings, rng: &mut impl Rng) {
+        self.timer = self.timer.saturating_sub(1);
+        self.attack_cooldown = self.attack_cooldown.saturating_sub(1);
+        self.attacked = false;
+
+        let player_distance = (position - perception.player).length_sq().sqrt();
+        let sees = self.can_see(position, perception, timings);
+
+        // once it's after them it doesn't need convincing again
+        if sees && (self.is_hunting() || self.behavior == Behavior::ATTACKING) {
+            self.awareness = 1.0;
+        } else if sees {
+            self.awareness = (self.awareness + Self::detection_rate(player_distance, perception, timings)).min(1.0);
+        } else {
+            self.awareness = (self.awareness - 1.0 / (timings.notice_ticks.max(1) as f32 * AWARENESS_DECAY)).max(0.0);
+        }
+
+        // spotting the player beats everything else
+        if sees && self.awareness >= 1.0 {
+            self.last_known = Some(perception.player);
+            if player_distance > 0.0 {
+                self.facing = (perception.player - position) / player_distance;
+            }
+
+            if player_distance <= timings.attack_range {
+                self.switch(Behavior::ATTACKING, 0);
+
+                if self.attack_cooldown == 0 {
+                    self.attacked = true;
+                    self.attack_cooldown = timings.attack_cooldown_ticks;
+                }
+            } else {
+                self.switch(Behavior::CHASING, 0);
+            }
+
+            return;
+        }
+
+        match self.behavior {
+            Behavior::CHASING | Behavior::ATTACKING => {
+                self.last_known = Some(perception.player);
+                self.switch(Behavior::LOSING_TRACK, timings.lose_track_ticks);
+            },
+            // still on their heels, then off to wherever they were last
+            Behavior::LOSING_TRACK => {
+                if self.timer == 0 {
+                    self.switch(Behavior::INVESTIGATING, timings.investigate_ticks);
+                } else {
+                    self.last_known = Some(perception.player);
+                }
+            },
+            // caught a glimpse, goes to have a closer look
+            _ if sees && self.awareness >= SUSPICIOUS => {
+                self.last_known = Some(perception.player);
+                self.switch(Behavior::INVESTIGATING, timings.investigate_ticks);
+            },
+            _ if perception.heard.is_some() => {
+                self.last_known = perception.heard;
+                self.switch(Behavior::INVESTIGATING, timings.investigate_ticks);
+            },
+            // poke around where it went until it gives up
+            Behavior::INVESTIGATING => {
+                if self.timer == 0 {
+                    self.switch(Behavior::IDLE, rng.gen_range(timings.idle_ticks.clone()));
+                }
+            },
+            Behavior::WANDERING => {
+                let arrived = !matches!(self.wander_target, Some(target) if (position - target).length_sq().sqrt() >= ARRIVE_DISTANCE);
+
+                if self.timer == 0 || arrived {
+                    self.switch(Behavior::IDLE, rng.gen_range(timings.idle_ticks.clone()));
+                }
+            },
+            Behavior::IDLE => {
+                if self.timer == 0 {
+                    let angle = rng.gen_range(0.0..std::f32::consts::TAU);
+                    let reach = rng.gen_range(0.0..=timings.wander_radius);
+
+                    self.wander_target = Some(position + Vector2f::new(angle.cos(), angle.sin()) * reach);
+                    self.switch(Behavior::WANDERING, rng.gen_range(timings.wander_ticks.clone()));
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn timings() -> BehaviorTimings {
+        BehaviorTimings {
+            idle_ticks: 10..=20,
+            wander_ticks: 30..=40,
+            wander_radius: 100.0,
+            investigate_ticks: 50,
+            lose_track_ticks: 5,
+            attack_cooldown_ticks: 3,
+            sight_range: 200.0,
+            attack_range: 20.0,
+            vision_angle: 90.0,
+            notice_ticks: 10,
+        }
+    }
+
+    fn unseen(player: Vector2f) -> Perception {
+        Perception { player, line_of_sight: false, heard: None, light: 1.0, crouching: false }
+    }
+
+    fn seen(player: Vector2f) -> Perception {
+        Perception { player, line_of_sight: true, heard: None, light: 1.0, crouching: false }
+    }
+
+    // ticks until it starts chasing a player that stays put, None if it never does
+    fn ticks_to_spot(brain: &mut Brain, position: Vector2f, perception: &Perception, rng: &mut StdRng) -> Option<u32> {
+        (1..=1000).find(|_| {
+            brain.think(position, perception, &timings(), rng);
+            brain.behavior == Behavior::CHASING
+        })
+    }
+
+    fn facing(direction: Vector2f) -> Brain {
+        Brain { facing: direction, ..Brain::new() }
+    }
+
+    fn think_for(brain: &mut Brain, ticks: u32, position: Vector2f, perception: &Perception, rng: &mut StdRng) {
+        for _ in 0..ticks {
+            brain.think(position, perception, &timings(), rng);
+        }
+    }
+
+    const ORIGIN: Vector2f = Vector2f::new(0.0, 0.0);
+
+    #[test]
+    fn idle_zombies_wander_off_and_come_back_to_idle() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut brain = Brain::new();
+        let far_away = unseen(Vector2f::new(5000.0, 0.0));
+
+        brain.think(ORIGIN, &far_away, &timings(), &mut rng);
+        assert_eq!(brain.behavior, Behavior::WANDERING);
+
+        let target = brain.wander_target.unwrap();
+        assert!((ORIGIN - target).length_sq().sqrt() <= 100.0);
+        assert_eq!(brain.goal(), Some(target));
+
+        // arriving ends the stroll
+        brain.think(target, &far_away, &timings(), &mut rng);
+        assert_eq!(brain.behavior, Behavior::IDLE);
+        assert!((10..=20).contains(&brain.timer));
+    }
+
+    #[test]
+    fn seeing_the_player_starts_a_chase_and_attacks_in_range() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let mut brain = facing(Vector2f::new(1.0, 0.0));
+
+        let player = Vector2f::new(150.0, 0.0);
+        assert!(ticks_to_spot(&mut brain, ORIGIN, &seen(player), &mut rng).is_some());
+        assert_eq!(brain.goal(), Some(player));
+
+        // out of sight range or behind a wall doesn't count
+        let mut other = facing(Vector2f::new(1.0, 0.0));
+        assert_eq!(ticks_to_spot(&mut other, ORIGIN, &seen(Vector2f::new(250.0, 0.0)), &mut rng), None);
+        assert_eq!(ticks_to_spot(&mut other, ORIGIN, &unseen(player), &mut rng), None);
+
+        let close = Vector2f::new(10.0, 0.0);
+        brain.think(ORIGIN, &seen(close), &timings(), &mut rng);
+        assert_eq!(brain.behavior, Behavior::ATTACKING);
+        assert!(brain.attacked);
+
+        // then waits out the cooldown between hits
+        let hits: usize = (0..9)
+            .filter(|_| {
+                brain.think(ORIGIN, &seen(close), &timings(), &mut rng);
+                brain.attacked
+            })
+            .count();
+        assert_eq!(hits, 3);
+    }
+
+    #[test]
+    fn lost_players_are_followed_then_investigated_then_given_up_on() {
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut brain = facing(Vector2f::new(1.0, 0.0));
+
+        ticks_to_spot(&mut brain, ORIGIN, &seen(Vector2f::new(100.0, 0.0)), &mut rng).unwrap();
+
+        // out of sight, but it keeps up with where they actually are for a few ticks
+        let hidden = Vector2f::new(120.0, 60.0);
+        brain.think(ORIGIN, &unseen(hidden), &timings(), &mut rng);
+        assert_eq!(brain.behavior, Behavior::LOSING_TRACK);
+        assert_eq!(brain.goal(), Some(hidden));
+
+        think_for(&mut brain, 5, ORIGIN, &unseen(hidden), &mut rng);
+        assert_eq!(brain.behavior, Behavior::INVESTIGATING);
+
+        // it doesn't learn where the player went anymore
+        let gone = Vector2f::new(900.0, 900.0);
+        think_for(&mut brain, 49, ORIGIN, &unseen(gone), &mut rng);
+        assert_eq!(brain.behavior, Behavior::INVESTIGATING);
+        assert_eq!(brain.goal(), Some(hidden));
+
+        brain.think(ORIGIN, &unseen(gone), &timings(), &mut rng);
+        assert_eq!(brain.behavior, Behavior::IDLE);
+    }
+
+    #[test]
+    fn noises_get_investigated() {
+        let mut rng = StdRng::seed_from_u64(4);
+        let mut brain = Brain::new();
+
+        let noise = Vector2f::new(-300.0, 40.0);
+        let perception = Perception { heard: Some(noise), ..unseen(Vector2f::new(5000.0, 0.0)) };
+        brain.think(ORIGIN, &perception, &timings(), &mut rng);
+
+        assert_eq!(brain.behavior, Behavior::INVESTIGATING);
+        assert_eq!(brain.goal(), Some(noise));
+        assert_eq!(brain.timer, 50);
+    }
+
+    #[test]
+    fn players_behind_it_go_unnoticed_unless_right_there() {
+        let mut rng = StdRng::seed_from_u64(5);
+
+        let mut brain = facing(Vector2f::new(1.0, 0.0));
+        assert_eq!(ticks_to_spot(&mut brain, ORIGIN, &seen(Vector2f::new(-100.0, 0.0)), &mut rng), None);
+        assert_eq!(ticks_to_spot(&mut brain, ORIGIN, &seen(Vector2f::new(100.0, 120.0)), &mut rng), None);
+
+        let mut brain = facing(Vector2f::new(1.0, 0.0));
+        assert!(ticks_to_spot(&mut brain, ORIGIN, &seen(Vector2f::new(-30.0, 0.0)), &mut rng).is_some());
+    }
+
+    #[test]
+    fn distance_darkness_and_crouching_slow_detection() {
+        let mut rng = StdRng::seed_from_u64(6);
+        let mut spot = |perception: Perception| ticks_to_spot(&mut facing(Vector2f::new(1.0, 0.0)), ORIGIN, &perception, &mut rng).unwrap();
+
+        let near = spot(seen(Vector2f::new(50.0, 0.0)));
+        let far = spot(seen(Vector2f::new(190.0, 0.0)));
+        let dark = spot(Perception { light: 0.3, ..seen(Vector2f::new(50.0, 0.0)) });
+        let crouching = spot(Perception { crouching: true, ..seen(Vector2f::new(50.0, 0.0)) });
+
+        assert!(near < far);
+        assert!(near < dark);
+        assert!(near < crouching);
+    }
+
+    #[test]
+    fn bumping_into_the_player_in_the_dark_gives_them_away() {
+        let mut rng = StdRng::seed_from_u64(8);
+        let mut brain = facing(Vector2f::new(-1.0, 0.0));
+        let hidden = Perception { light: 0.0, crouching: true, ..seen(Vector2f::new(15.0, 0.0)) };
+
+        think_for(&mut brain, 20, ORIGIN, &hidden, &mut rng);
+        assert_eq!(brain.awareness, 0.0);
+
+        brain.touched_player();
+        brain.think(ORIGIN, &hidden, &timings(), &mut rng);
+        assert_eq!(brain.behavior, Behavior::ATTACKING);
+        assert!(brain.attacked);
+    }
+
+    #[test]
+    fn glimpses_get_a_closer_look_and_awareness_fades() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let mut brain = facing(Vector2f::new(1.0, 0.0));
+        let player = Vector2f::new(150.0, 0.0);
+
+        while brain.awareness < SUSPICIOUS {
+            brain.think(ORIGIN, &seen(player), &timings(), &mut rng);
+        }
+        assert_eq!(brain.behavior, Behavior::INVESTIGATING);
+        assert_eq!(brain.goal(), Some(player));
+
+        // ducking out of view before it's sure lets it calm back down
+        think_for(&mut brain, 20, ORIGIN, &unseen(player), &mut rng);
+        assert_eq!(brain.awareness, 0.0);
+        assert_eq!(brain.behavior, Behavior::INVESTIGATING);
+    }
+}
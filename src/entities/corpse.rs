@@ -0,0 +1,66 @@
+use std::sync::Arc;
+
+use sfml::{graphics::{Color, RenderTarget, RenderWindow, Sprite, Transformable}, system::Vector2f};
+
+use crate::{assets::Assets, states::game_state::{KeyboardData, MouseData}};
+use super::{
+    entity::{Entity, EntityType, ENTITY_SIZE},
+    zombie::ZOMBIE_IMG,
+};
+
+// what's left of a zombie. lies where it fell and doesn't get in anyone's way
+pub struct Corpse {
+    pub x: f32,
+    pub y: f32,
+
+    pub assets: Arc<Assets>,
+}
+
+impl Entity for Corpse {
+    fn move_towards_position(&mut self, _: Vector2f) { }
+
+    fn move_entity(&mut self, _: f32, _: f32) { }
+
+    fn get_type(&self) -> EntityType {
+        EntityType::CORPSE
+    }
+
+    fn get_speed(&self) -> f32 { 0.0 }
+
+    fn get_position(&self) -> Vector2f {
+        Vector2f::new(self.x, self.y)
+    }
+
+    fn set_position(&mut self, position: Vector2f) {
+        self.x = position.x;
+        self.y = position.y;
+    }
+
+    fn get_radius(&self) -> f32 { 0.0 }
+
+    fn can_swim(&self) -> bool { true }
+
+    fn update(&mut self, _: Vector2f, _: KeyboardData, _: MouseData) { }
+
+    fn render(&self, window: &mut RenderWindow, camera_offset: Vector2f) {
+        let mut sprite = Sprite::new();
+        sprite.set_texture(&self.assets.zombie_texture, true);
+        sprite.set_texture_rect(ZOMBIE_IMG);
+        // on its side and drained of colour
+        sprite.set_origin(Vector2f::new((ENTITY_SIZE / 2) as f32, (ENTITY_SIZE / 2) as f32));
+        sprite.set_rotation(90.0);
+        sprite.set_color(Color::rgb(110, 90, 90));
+        sprite.set_position(self.get_position() - camera_offset);
+        window.draw(&sprite);
+    }
+}
+
+impl Corpse {
+    pub fn new(assets: Arc<Assets>, position: Vector2f) -> Self {
+        Corpse {
+            x: position.x,
+            y: position.y,
+            assets,
+        }
+    }
+}
@@ -1,18 +1,18 @@
 use crate::states::game_state::{KeyboardData, MouseData};
+use crate::{ballistics::Shot, melee::Swing, sound::SoundEvent};
+use super::{brain::Perception, health::Health};
 use sfml::{graphics::RenderWindow, system::Vector2f};
 
 pub const ENTITY_SIZE: usize = 64;
+// width and height of the box entities collide with, centred on their position. has to stay
+// under a tile so they fit through doors
+pub const HITBOX_SIZE: f32 = 20.0;
 
-#[derive(PartialEq)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum EntityType {
     PLAYER = 0,
     ZOMBIE = 1,
-}
-
-#[derive(PartialEq)]
-pub enum Behavior {
-    STATIC = 0,
-    CHASING = 1,
+    CORPSE = 2,
 }
 
 pub trait Entity {
@@ -21,6 +21,36 @@ pub trait Entity {
     fn get_type(&self) -> EntityType;
     fn get_speed(&self) -> f32;
     fn get_position(&self) -> Vector2f;
+    fn set_position(&mut self, position: Vector2f);
+    fn can_swim(&self) -> bool;
+    // harder to spot while crouching
+    fn is_crouching(&self) -> bool { false }
+    // entities collide with each other as circles of this radius
+    fn get_radius(&self) -> f32;
+    // None for things that can't be hurt
+    fn get_health(&self) -> Option<&Health> { None }
+    fn get_health_mut(&mut self) -> Option<&mut Health> { None }
+    fn is_dead(&self) -> bool {
+        matches!(self.get_health(), Some(health) if health.is_dead())
+    }
+    // the swing it landed since the last call, collected after every update
+    fn take_swing(&mut self) -> Option<Swing> { None }
+    // called once per tick for every entity it's touching
+    fn on_contact(&mut self, _other: EntityType) {}
+    // what it can see and hear this tick, handed out before update
+    fn perceive(&mut self, _perception: Perception) {}
+    // which way the flow field says the player is from here, handed out every tick
+    fn set_flow_direction(&mut self, _direction: Option<Vector2f>) {}
+    // where it would like a path to, None while it doesn't need a new one
+    fn wants_path(&self) -> Option<Vector2f> { None }
+    // waypoints in world space, empty if there's no way there
+    fn set_path(&mut self, _path: Vec<Vector2f>) {}
+    // shots it fired since the last call, collected after every update
+    fn take_shots(&mut self) -> Vec<Shot> { Vec::new() }
+    // sounds it made since the last call, collected after every update
+    fn take_sounds(&mut self) -> Vec<SoundEvent> { Vec::new() }
+    // fills up whatever drinking containers it carries, for when it's standing by water
+    fn refill_water(&mut self) {}
     fn update(&mut self, reference_position: Vector2f, key_data: KeyboardData, mouse_data: MouseData);
     fn render(&self, window: &mut RenderWindow, camera_offset: Vector2f);
 }
\ No newline at end of file
@@ -0,0 +1,256 @@
+use sfml::system::Vector2f;
+
+use super::entity::{Entity, EntityType};
+
+// health lost per tick for every point of damage a wound was opened with
+const BULLET_BLEED: f32 = 0.004;
+const MELEE_BLEED: f32 = 0.002;
+// how much a wound's bleeding slows down every tick until it stops
+const CLOT_RATE: f32 = 0.0005;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum DamageKind {
+    BULLET = 0,
+    MELEE = 1,
+    // from open wounds, a little every tick
+    BLEED = 2,
+    FALL = 3,
+}
+
+impl DamageKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DamageKind::BULLET => "gunshot",
+            DamageKind::MELEE => "mauling",
+            DamageKind::BLEED => "blood loss",
+            DamageKind::FALL => "fall",
+        }
+    }
+
+    // bleeding per tick opened by each point of damage
+    fn bleed_rate(&self) -> f32 {
+        match self {
+            DamageKind::BULLET => BULLET_BLEED,
+            DamageKind::MELEE => MELEE_BLEED,
+            DamageKind::BLEED | DamageKind::FALL => 0.0,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Damage {
+    pub amount: f32,
+    pub kind: DamageKind,
+    // where it came from, None for things like bleeding
+    pub source: Option<Vector2f>,
+}
+
+impl Damage {
+    pub fn new(amount: f32, kind: DamageKind, source: Option<Vector2f>) -> Self {
+        Damage { amount, kind, source }
+    }
+}
+
+// damage waiting to be dealt to entities[target] at the end of the tick
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct DamageEvent {
+    pub target: usize,
+    pub damage: Damage,
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+    // health lost every tick to open wounds
+    pub bleeding: f32,
+    // whatever hurt it last, so a death can say what did it
+    pub last_damage: Option<DamageKind>,
+}
+
+impl Health {
+    pub fn new(max: f32) -> Self {
+        Health {
+            current: max,
+            max,
+            bleeding: 0.0,
+            last_damage: None,
+        }
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.current <= 0.0
+    }
+
+    // returns how much health was actually lost. the dead can't be hurt any more
+    pub fn apply(&mut self, damage: Damage) -> f32 {
+        if self.is_dead() || damage.amount <= 0.0 {
+            return 0.0;
+        }
+
+        let lost = damage.amount.min(self.current);
+        self.current -= lost;
+        self.bleeding += damage.amount * damage.kind.bleed_rate();
+        self.last_damage = Some(damage.kind);
+
+        lost
+    }
+
+    pub fn heal(&mut self, amount: f32) {
+        if !self.is_dead() {
+            self.current = (self.current + amount).min(self.max);
+        }
+    }
+
+    // once per tick, bleeds and lets wounds close up a little
+    pub fn update(&mut self) {
+        if self.bleeding <= 0.0 {
+            return;
+        }
+
+        self.apply(Damage::new(self.bleeding, DamageKind::BLEED, None));
+        self.bleeding = (self.bleeding - CLOT_RATE).max(0.0);
+    }
+}
+
+// something that died while damage was being dealt
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Death {
+    pub index: usize,
+    pub entity_type: EntityType,
+    pub position: Vector2f,
+    pub cause: DamageKind,
+}
+
+// deals out the tick's damage, bleeds everyone and reports who died. events are used up
+pub fn resolve_damage(entities: &mut [Box<dyn Entity>], events: &mut Vec<DamageEvent>) -> Vec<Death> {
+    let alive_before: Vec<bool> = entities.iter().map(|entity| !entity.is_dead()).collect();
+
+    for event in events.drain(..) {
+        if let Some(health) = entities.get_mut(event.target).and_then(|entity| entity.get_health_mut()) {
+            health.apply(event.damage);
+        }
+    }
+
+    for entity in entities.iter_mut() {
+        if let Some(health) = entity.get_health_mut() {
+            health.update();
+        }
+    }
+
+    entities
+        .iter()
+        .enumerate()
+        .filter(|(index, entity)| alive_before[*index] && entity.is_dead())
+        .map(|(index, entity)| Death {
+            index,
+            entity_type: entity.get_type(),
+            position: entity.get_position(),
+            cause: entity.get_health().and_then(|health| health.last_damage).unwrap_or(DamageKind::BLEED),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use sfml::graphics::RenderWindow;
+
+    use crate::states::game_state::{KeyboardData, MouseData};
+
+    use super::*;
+
+    // just enough of an entity to take hits, never rendered
+    struct Dummy {
+        entity_type: EntityType,
+        health: Option<Health>,
+    }
+
+    impl Entity for Dummy {
+        fn move_towards_position(&mut self, _: Vector2f) {}
+        fn move_entity(&mut self, _: f32, _: f32) {}
+        fn get_type(&self) -> EntityType { self.entity_type }
+        fn get_speed(&self) -> f32 { 0.0 }
+        fn get_position(&self) -> Vector2f { Vector2f::new(0.0, 0.0) }
+        fn set_position(&mut self, _: Vector2f) {}
+        fn can_swim(&self) -> bool { false }
+        fn get_radius(&self) -> f32 { 10.0 }
+        fn get_health(&self) -> Option<&Health> { self.health.as_ref() }
+        fn get_health_mut(&mut self) -> Option<&mut Health> { self.health.as_mut() }
+        fn update(&mut self, _: Vector2f, _: KeyboardData, _: MouseData) {}
+        fn render(&self, _: &mut RenderWindow, _: Vector2f) {}
+    }
+
+    fn dummy(entity_type: EntityType, health: Option<f32>) -> Box<dyn Entity> {
+        Box::new(Dummy { entity_type, health: health.map(Health::new) })
+    }
+
+    fn hit(target: usize, amount: f32, kind: DamageKind) -> DamageEvent {
+        DamageEvent { target, damage: Damage::new(amount, kind, None) }
+    }
+
+    #[test]
+    fn damage_stops_at_zero_and_the_dead_stay_dead() {
+        let mut health = Health::new(50.0);
+
+        assert_eq!(health.apply(Damage::new(20.0, DamageKind::FALL, None)), 20.0);
+        assert_eq!(health.current, 30.0);
+        assert_eq!(health.bleeding, 0.0);
+
+        assert_eq!(health.apply(Damage::new(100.0, DamageKind::MELEE, None)), 30.0);
+        assert!(health.is_dead());
+        assert_eq!(health.last_damage, Some(DamageKind::MELEE));
+
+        health.heal(10.0);
+        assert_eq!(health.apply(Damage::new(5.0, DamageKind::BULLET, None)), 0.0);
+        assert!(health.is_dead());
+    }
+
+    #[test]
+    fn wounds_bleed_out_slowly_and_close() {
+        let mut health = Health::new(100.0);
+        health.apply(Damage::new(20.0, DamageKind::BULLET, None));
+        assert!(health.bleeding > 0.0);
+
+        let after_hit = health.current;
+        health.update();
+        assert!(health.current < after_hit);
+        assert_eq!(health.last_damage, Some(DamageKind::BLEED));
+
+        for _ in 0..1000 {
+            health.update();
+        }
+        assert_eq!(health.bleeding, 0.0);
+        assert!(!health.is_dead());
+    }
+
+    #[test]
+    fn resolving_reports_each_death_once() {
+        let mut entities = vec![
+            dummy(EntityType::PLAYER, Some(100.0)),
+            dummy(EntityType::ZOMBIE, Some(40.0)),
+            dummy(EntityType::CORPSE, None),
+        ];
+
+        let mut events = vec![hit(1, 25.0, DamageKind::BULLET), hit(0, 10.0, DamageKind::MELEE), hit(2, 50.0, DamageKind::BULLET)];
+        assert!(resolve_damage(&mut entities, &mut events).is_empty());
+        assert!(events.is_empty());
+        assert!(entities[0].get_health().unwrap().current < 100.0);
+
+        let mut events = vec![hit(1, 25.0, DamageKind::BULLET), hit(1, 25.0, DamageKind::BULLET), hit(7, 5.0, DamageKind::FALL)];
+        let deaths = resolve_damage(&mut entities, &mut events);
+        assert_eq!(deaths.len(), 1);
+        assert_eq!((deaths[0].index, deaths[0].entity_type, deaths[0].cause), (1, EntityType::ZOMBIE, DamageKind::BULLET));
+
+        assert!(resolve_damage(&mut entities, &mut Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn bleeding_out_counts_as_a_death() {
+        let mut entities = vec![dummy(EntityType::PLAYER, Some(10.0))];
+        resolve_damage(&mut entities, &mut vec![hit(0, 9.5, DamageKind::BULLET)]);
+
+        let deaths: Vec<Death> = (0..100).flat_map(|_| resolve_damage(&mut entities, &mut Vec::new())).collect();
+        assert_eq!(deaths.len(), 1);
+        assert_eq!(deaths[0].cause, DamageKind::BLEED);
+    }
+}
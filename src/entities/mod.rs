@@ -1,3 +1,6 @@
+pub mod brain;
+pub mod corpse;
 pub mod entity;
+pub mod health;
 pub mod player;
 pub mod zombie;
\ No newline at end of file
@@ -1,18 +1,58 @@
-use std::{f32::consts::SQRT_2, sync::Arc, time::Instant};
+use std::{f32::consts::SQRT_2, sync::Arc};
 
 use sfml::{graphics::{IntRect, Rect, RenderTarget, RenderWindow, Sprite, Transformable}, system::{Vector2f, Vector2i}};
 use kira::manager::{AudioManager, AudioManagerSettings};
 
-use crate::{assets::Assets, states::game_state::{KeyboardData, MouseData}};
-use super::entity::{Entity, EntityType, ENTITY_SIZE};
+use crate::{
+    assets::Assets,
+    ballistics::Shot,
+    item::DrinkingContainer,
+    melee::{Melee, Swing, SwingStats},
+    sound::{SoundEvent, SPRINT_LOUDNESS},
+    states::game_state::{KeyboardData, MouseData},
+    weapon::{Weapon, WeaponEvent},
+};
+use super::{entity::{Entity, EntityType, ENTITY_SIZE, HITBOX_SIZE}, health::Health};
 
 pub const PLAYER_RECT: IntRect = Rect::new(0, 0, ENTITY_SIZE as i32, ENTITY_SIZE as i32);
+// crouching trades speed for being harder to spot
+const CROUCH_SPEED: f32 = 0.5;
+const PLAYER_HEALTH: f32 = 100.0;
+const STARTING_WEAPON: &str = "handgun";
+const MAX_STAMINA: f32 = 100.0;
+// per tick while not swinging, a second and a half from empty to full
+const STAMINA_REGEN: f32 = 100.0 / 150.0;
+// hitting things with the gun, for when the ammo's running out
+const PLAYER_SWING: SwingStats = SwingStats {
+    windup_ticks: 12,
+    recovery_ticks: 28,
+    reach: 30.0,
+    arc: 110.0,
+    damage: 20.0,
+    knockback: 20.0,
+    stamina_cost: 30.0,
+};
+// sprinting is quicker but loud
+const SPRINT_SPEED: f32 = 1.6;
+// ticks between footsteps loud enough to hear while sprinting
+const SPRINT_STEP_TICKS: u32 = 20;
 
 pub struct Player {
     pub x: f32,
     pub y: f32,
 
-    pub firing_cooldown: Instant,
+    pub weapon: Weapon,
+    pub melee: Melee,
+    pub stamina: f32,
+    swing: Option<Swing>,
+    pub crouching: bool,
+    pub sprinting: bool,
+    step_timer: u32,
+    sounds: Vec<SoundEvent>,
+    shots: Vec<Shot>,
+
+    pub containers: Vec<DrinkingContainer>,
+    pub health: Health,
 
     pub assets: Arc<Assets>,
     pub audio_manager: AudioManager,
@@ -39,26 +79,101 @@ impl Entity for Player {
         EntityType::PLAYER
     }
 
-    fn get_speed(&self) -> f32 { 0.5 }
+    fn get_speed(&self) -> f32 {
+        if self.crouching {
+            0.5 * CROUCH_SPEED
+        } else if self.sprinting {
+            0.5 * SPRINT_SPEED
+        } else {
+            0.5
+        }
+    }
 
     fn get_position(&self) -> Vector2f {
         Vector2f::new(self.x, self.y)
     }
 
+    fn set_position(&mut self, position: Vector2f) {
+        self.x = position.x;
+        self.y = position.y;
+    }
+
+    fn get_radius(&self) -> f32 { HITBOX_SIZE / 2.0 }
+
+    fn can_swim(&self) -> bool { true }
+
+    fn is_crouching(&self) -> bool { self.crouching }
+
+    fn get_health(&self) -> Option<&Health> { Some(&self.health) }
+
+    fn get_health_mut(&mut self) -> Option<&mut Health> { Some(&mut self.health) }
+
+    fn take_shots(&mut self) -> Vec<Shot> {
+        std::mem::take(&mut self.shots)
+    }
+
+    fn take_swing(&mut self) -> Option<Swing> {
+        self.swing.take()
+    }
+
+    fn take_sounds(&mut self) -> Vec<SoundEvent> {
+        std::mem::take(&mut self.sounds)
+    }
+
+    fn refill_water(&mut self) {
+        for container in self.containers.iter_mut() {
+            container.refill();
+        }
+    }
+
     fn update(&mut self, _: Vector2f, key_data: KeyboardData, mouse_data: MouseData) {
-        if mouse_data.left_click {
-            if self.firing_cooldown.elapsed().as_millis() >= 1000 {
-                self.audio_manager.play(self.assets.handgun_fire.clone()).unwrap();
-                self.firing_cooldown = Instant::now();
+        let mut events = Vec::new();
+        if key_data.reload { events.extend(self.weapon.reload()) }
+        if key_data.chamber { events.extend(self.weapon.chamber()) }
+        if key_data.unload { events.extend(self.weapon.unload()) }
+        // can't shoot mid swing
+        events.extend(self.weapon.update(mouse_data.left_click && self.melee.is_ready()));
+
+        for event in events {
+            if let Some(sound) = self.weapon.sound(event).and_then(|name| self.assets.sounds.get(name)) {
+                self.audio_manager.play(sound.clone()).unwrap();
             }
+
+            if event == WeaponEvent::FIRED {
+                self.sounds.push(SoundEvent::new(self.get_position(), self.weapon.definition.loudness));
+
+                let aim = mouse_data.world_position - self.get_position();
+                if aim.length_sq() > 0.0 {
+                    self.shots.extend(self.weapon.shots(self.get_position(), aim, &mut rand::thread_rng()));
+                }
+            }
+        }
+
+        let aim = mouse_data.world_position - self.get_position();
+        if mouse_data.right_click && self.stamina >= PLAYER_SWING.stamina_cost && self.melee.start(aim) {
+            self.stamina -= PLAYER_SWING.stamina_cost;
+        }
+        self.swing = self.melee.update(self.get_position(), EntityType::PLAYER);
+        if self.melee.is_ready() {
+            self.stamina = (self.stamina + STAMINA_REGEN).min(MAX_STAMINA);
         }
 
+        self.crouching = key_data.crouch;
+        self.sprinting = key_data.sprint && !self.crouching;
+
         let (mut x_move, mut y_move) = (0.0, 0.0);
         if key_data.w { y_move -= self.get_speed() }
         if key_data.s { y_move += self.get_speed() }
         if key_data.a { x_move -= self.get_speed() }
         if key_data.d { x_move += self.get_speed() }
         self.move_entity(x_move, y_move);
+
+        let moving = x_move != 0.0 || y_move != 0.0;
+        self.step_timer = self.step_timer.saturating_sub(1);
+        if self.sprinting && moving && self.step_timer == 0 {
+            self.sounds.push(SoundEvent::new(self.get_position(), SPRINT_LOUDNESS));
+            self.step_timer = SPRINT_STEP_TICKS;
+        }
     }
 
     fn render(&self, window: &mut RenderWindow, camera_offset: Vector2f) {
@@ -80,7 +195,17 @@ impl Player {
         Player {
             x: 32.0,
             y: 32.0,
-            firing_cooldown: Instant::now(),
+            weapon: Weapon::new(assets.weapons.get(STARTING_WEAPON).expect("No starting weapon").clone()),
+            melee: Melee::new(PLAYER_SWING),
+            stamina: MAX_STAMINA,
+            swing: None,
+            crouching: false,
+            sprinting: false,
+            step_timer: 0,
+            sounds: Vec::new(),
+            shots: Vec::new(),
+            containers: vec![DrinkingContainer::water_bottle()],
+            health: Health::new(PLAYER_HEALTH),
             assets,
             audio_manager: AudioManager::new(AudioManagerSettings::default()).unwrap(),
         }
@@ -1,18 +1,138 @@
 use std::sync::Arc;
 
 use kira::manager::{AudioManager, AudioManagerSettings};
+use rand::{rngs::StdRng, SeedableRng};
 use sfml::{graphics::{IntRect, Rect, RenderTarget, RenderWindow, Sprite, Transformable}, system::Vector2f};
 
-use crate::{assets::Assets, states::game_state::{KeyboardData, MouseData}, util::UtilFunctions};
-use super::entity::{Behavior, Entity, EntityType, ENTITY_SIZE};
+use crate::{
+    assets::Assets,
+    map::TILE_SIZE,
+    melee::{Melee, Swing, SwingStats},
+    states::game_state::{KeyboardData, MouseData},
+    util::UtilFunctions,
+};
+use super::{
+    brain::{Behavior, BehaviorTimings, Brain, Perception},
+    entity::{Entity, EntityType, ENTITY_SIZE, HITBOX_SIZE},
+    health::Health,
+};
 
 pub const ZOMBIE_IMG: IntRect = Rect::new(0, 0, ENTITY_SIZE as i32, ENTITY_SIZE as i32);
 
+// ticks between asking for a new path, and how far the target has to wander off the end of
+// the current one before it's worth asking early
+const REPATH_TICKS: u32 = 45;
+const REPATH_DISTANCE: f32 = 64.0;
+// close enough to a waypoint to head for the next one
+const WAYPOINT_REACHED: f32 = 6.0;
+// nobody's in a hurry when there's nothing to chase
+const WANDER_SPEED: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum ZombieKind {
+    WALKER = 0,
+    RUNNER = 1,
+}
+
+impl ZombieKind {
+    pub fn speed(&self) -> f32 {
+        match self {
+            ZombieKind::WALKER => 0.25,
+            ZombieKind::RUNNER => 0.4,
+        }
+    }
+
+    pub fn max_health(&self) -> f32 {
+        match self {
+            ZombieKind::WALKER => 100.0,
+            ZombieKind::RUNNER => 60.0,
+        }
+    }
+
+    // has to fit inside attack_cooldown_ticks, and reach past attack_range
+    pub fn swing(&self) -> SwingStats {
+        match self {
+            ZombieKind::WALKER => SwingStats {
+                windup_ticks: 30,
+                recovery_ticks: 20,
+                reach: 28.0,
+                arc: 90.0,
+                damage: 15.0,
+                knockback: 6.0,
+                stamina_cost: 0.0,
+            },
+            ZombieKind::RUNNER => SwingStats {
+                windup_ticks: 15,
+                recovery_ticks: 15,
+                reach: 28.0,
+                arc: 100.0,
+                damage: 8.0,
+                knockback: 4.0,
+                stamina_cost: 0.0,
+            },
+        }
+    }
+
+    pub fn timings(&self) -> BehaviorTimings {
+        match self {
+            ZombieKind::WALKER => BehaviorTimings {
+                idle_ticks: 120..=360,
+                wander_ticks: 180..=300,
+                wander_radius: 96.0,
+                investigate_ticks: 600,
+                lose_track_ticks: 180,
+                attack_cooldown_ticks: 60,
+                sight_range: 256.0,
+                attack_range: 24.0,
+                vision_angle: 120.0,
+                notice_ticks: 45,
+            },
+            ZombieKind::RUNNER => BehaviorTimings {
+                idle_ticks: 60..=180,
+                wander_ticks: 120..=240,
+                wander_radius: 160.0,
+                investigate_ticks: 420,
+                lose_track_ticks: 120,
+                attack_cooldown_ticks: 40,
+                sight_range: 320.0,
+                attack_range: 24.0,
+                vision_angle: 140.0,
+                notice_ticks: 30,
+            },
+        }
+    }
+
+    // walkers sink, runners thrash their way across
+    pub fn can_swim(&self) -> bool {
+        match self {
+            ZombieKind::WALKER => false,
+            ZombieKind::RUNNER => true,
+        }
+    }
+}
+
 pub struct Zombie {
     pub x: f32,
     pub y: f32,
     
-    pub behavior: Behavior,
+    pub kind: ZombieKind,
+    pub brain: Brain,
+    pub health: Health,
+    pub melee: Melee,
+    perception: Perception,
+    swing: Option<Swing>,
+    rng: StdRng,
+
+    // towards the player along the flow field, None when it's off the field. takes priority
+    // over the path since it's free
+    pub flow_direction: Option<Vector2f>,
+    // waypoints towards target, nearest first. walks straight at the target once it runs out
+    pub path: Vec<Vector2f>,
+    target: Vector2f,
+    // where target was when the current path was made
+    path_goal: Option<Vector2f>,
+    repath_timer: u32,
+
     pub assets: Arc<Assets>,
     pub audio_manager: AudioManager,
 }
@@ -22,56 +142,149 @@ impl Entity for Zombie {
         EntityType::ZOMBIE
     }
 
-    fn get_speed(&self) -> f32 { 0.25 }
+    fn get_speed(&self) -> f32 { self.kind.speed() }
 
     fn get_position(&self) -> Vector2f {
         Vector2f::new(self.x, self.y)
     }
 
+    fn set_position(&mut self, position: Vector2f) {
+        self.x = position.x;
+        self.y = position.y;
+    }
+
+    fn get_radius(&self) -> f32 { HITBOX_SIZE / 2.0 }
+
+    fn can_swim(&self) -> bool { self.kind.can_swim() }
+
+    fn get_health(&self) -> Option<&Health> { Some(&self.health) }
+
+    fn get_health_mut(&mut self) -> Option<&mut Health> { Some(&mut self.health) }
+
+    fn take_swing(&mut self) -> Option<Swing> {
+        self.swing.take()
+    }
+
     fn move_entity(&mut self, _: f32, _: f32) { }
 
     fn move_towards_position(&mut self, position: Vector2f) {
+        self.step_towards(position, self.get_speed());
+    }
 
-        let angle = UtilFunctions::get_angle(position, self.get_position());
+    fn perceive(&mut self, perception: Perception) {
+        self.perception = perception;
+    }
 
-        let x = self.get_speed() as f64 * libm::cos(angle);
-        let y = self.get_speed() as f64 * libm::sin(angle);
+    fn on_contact(&mut self, other: EntityType) {
+        if other == EntityType::PLAYER {
+            self.brain.touched_player();
+        }
+    }
 
-        self.x += x as f32;
-        self.y += y as f32;
+    fn set_flow_direction(&mut self, direction: Option<Vector2f>) {
+        self.flow_direction = direction;
     }
 
-    fn update(&mut self, reference_position: Vector2f, _: KeyboardData, _: MouseData) {
-        match self.behavior {
-            Behavior::STATIC => { },
-            Behavior::CHASING => self.move_towards_position(reference_position), 
+    // the flow field covers the player, everything else needs a path
+    fn wants_path(&self) -> Option<Vector2f> {
+        if self.brain.goal().is_none() || (self.brain.is_hunting() && self.flow_direction.is_some()) || self.repath_timer > 0 {
+            return None;
+        }
+
+        match self.path_goal {
+            Some(goal) if (goal - self.target).length_sq().sqrt() < REPATH_DISTANCE && !self.path.is_empty() => None,
+            _ => Some(self.target),
         }
-        
-        let delta = self.get_position() - reference_position;
-        self.behavior = if delta.x.abs() < 256.0 && delta.y.abs() < 256.0 {
-            Behavior::CHASING
-        } else {
-            Behavior::STATIC
+    }
+
+    fn set_path(&mut self, path: Vec<Vector2f>) {
+        self.path = path;
+        self.path_goal = Some(self.target);
+        self.repath_timer = REPATH_TICKS;
+    }
+
+    fn update(&mut self, _: Vector2f, _: KeyboardData, _: MouseData) {
+        let timings = self.kind.timings();
+        self.brain.think(self.get_position(), &self.perception, &timings, &mut self.rng);
+
+        // the swing is aimed where the player was when it started, they can still step out of it
+        if self.brain.attacked {
+            let aim = self.perception.player - self.get_position();
+            if self.melee.start(aim) {
+                self.brain.facing = self.melee.facing;
+            }
+        }
+        self.swing = self.melee.update(self.get_position(), EntityType::ZOMBIE);
+
+        self.repath_timer = self.repath_timer.saturating_sub(1);
+
+        let Some(goal) = self.brain.goal() else {
+            self.path.clear();
+            return;
         };
+        self.target = goal;
+
+        let speed = match self.brain.behavior {
+            Behavior::WANDERING => self.get_speed() * WANDER_SPEED,
+            _ => self.get_speed(),
+        };
+
+        if let Some(direction) = self.flow_direction.filter(|_| self.brain.is_hunting()) {
+            self.step_towards(self.get_position() + direction * TILE_SIZE as f32, speed);
+            return;
+        }
+
+        if self.path.first().is_some_and(|waypoint| (*waypoint - self.get_position()).length_sq().sqrt() < WAYPOINT_REACHED) {
+            self.path.remove(0);
+        }
+
+        let next = self.path.first().copied().unwrap_or(goal);
+        self.step_towards(next, speed);
     }
 
     fn render(&self, window: &mut RenderWindow, camera_offset: Vector2f) {
         let mut sprite = Sprite::new();
         sprite.set_texture(&self.assets.zombie_texture, true);
         sprite.set_texture_rect(ZOMBIE_IMG);
-        sprite.set_position(self.get_position() - camera_offset);
+        // centred on the position like the player, that's where its hitbox is
+        sprite.set_position(self.get_position() - camera_offset - Vector2f::new((ENTITY_SIZE / 2) as f32, (ENTITY_SIZE / 2) as f32));
         window.draw(&sprite);
     }
 }
 
 impl Zombie {
-    pub fn new(assets: Arc<Assets>) -> Self {
+    fn step_towards(&mut self, position: Vector2f, speed: f32) {
+        // already there, atan2 of nothing would send it off sideways
+        if (position - self.get_position()).length_sq().sqrt() < speed {
+            return;
+        }
+
+        let angle = UtilFunctions::get_angle(position, self.get_position());
+        self.brain.facing = Vector2f::new(libm::cos(angle) as f32, libm::sin(angle) as f32);
+
+        self.x += (speed as f64 * libm::cos(angle)) as f32;
+        self.y += (speed as f64 * libm::sin(angle)) as f32;
+    }
+
+    // seed drives everything random it does, like where it wanders off to
+    pub fn new(assets: Arc<Assets>, kind: ZombieKind, position: Vector2f, seed: u64) -> Self {
         Zombie {
-            x: 256.0,
-            y: 256.0,
-            behavior: Behavior::STATIC,
+            x: position.x,
+            y: position.y,
+            kind,
+            brain: Brain::new(),
+            health: Health::new(kind.max_health()),
+            melee: Melee::new(kind.swing()),
+            perception: Perception { player: Vector2f::new(0.0, 0.0), line_of_sight: false, heard: None, light: 1.0, crouching: false },
+            swing: None,
+            rng: StdRng::seed_from_u64(seed),
+            flow_direction: None,
+            path: Vec::new(),
+            target: Vector2f::new(0.0, 0.0),
+            path_goal: None,
+            repath_timer: 0,
             assets,
             audio_manager: AudioManager::new(AudioManagerSettings::default()).unwrap(),
         }
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,228 @@
+use std::collections::{BinaryHeap, HashMap};
+
+use sfml::system::Vector2f;
+
+use crate::{
+    map::{world_to_tile, TILE_SIZE},
+    pathfinding::{can_step, octile, Node, DIRECTIONS},
+};
+
+// tiles out from the goal the field reaches, about as far as chunks stay loaded
+pub const FLOW_FIELD_RADIUS: i32 = 40;
+// tiles settled per tick, a full field takes a handful of ticks
+pub const DEFAULT_FLOW_FIELD_BUDGET: usize = 2000;
+// rebuilt this often even if the goal stays put, so opened doors and edited tiles show up
+const REBUILD_TICKS: u32 = 120;
+
+// a Dijkstra map being filled in
+struct Build {
+    goal: (i32, i32),
+    distances: HashMap<(i32, i32), f32>,
+    open: BinaryHeap<Node>,
+}
+
+// walking distance from every reachable tile around the goal to the goal. built a few
+// thousand tiles per tick in the background: the last finished field keeps answering until
+// the next one is done, so a moving goal never leaves anyone without directions. a build
+// always finishes before the next one starts from wherever the goal has got to, otherwise a
+// goal that never sits still would never get a field at all
+pub struct FlowField {
+    pub budget: usize,
+    goal: Option<(i32, i32)>,
+    distances: HashMap<(i32, i32), f32>,
+    building: Option<Build>,
+    age: u32,
+}
+
+impl FlowField {
+    pub fn new(budget: usize) -> Self {
+        FlowField {
+            budget,
+            goal: None,
+            distances: HashMap::new(),
+            building: None,
+            age: 0,
+        }
+    }
+
+    pub fn update(&mut self, goal_position: Vector2f, is_blocked: impl Fn(i32, i32) -> bool) {
+        let goal = world_to_tile(goal_position);
+        self.age += 1;
+
+        let outdated = self.goal != Some(goal) || self.age >= REBUILD_TICKS;
+
+        if self.building.is_none() && outdated {
+            self.building = Some(Build {
+                goal,
+                distances: HashMap::from([(goal, 0.0)]),
+                open: BinaryHeap::from([Node { cost: 0.0, tile: goal }]),
+            });
+        }
+
+        let Some(build) = self.building.as_mut() else {
+            return;
+        };
+
+        let mut settled = 0;
+        while settled < self.budget {
+            let Some(Node { cost, tile }) = build.open.pop() else {
+                break;
+            };
+
+            // stale entry, this tile was already reached cheaper
+            if cost > build.distances[&tile] {
+                continue;
+            }
+            settled += 1;
+
+            for direction in DIRECTIONS {
+                let next = (tile.0 + direction.0, tile.1 + direction.1);
+                let out_of_range = (next.0 - build.goal.0).abs() > FLOW_FIELD_RADIUS || (next.1 - build.goal.1).abs() > FLOW_FIELD_RADIUS;
+
+                if out_of_range || !can_step(tile, direction, &is_blocked) {
+                    continue;
+                }
+
+                let next_cost = cost + octile(tile, next);
+                if !matches!(build.distances.get(&next), Some(known) if *known <= next_cost) {
+                    build.distances.insert(next, next_cost);
+                    build.open.push(Node { cost: next_cost, tile: next });
+                }
+            }
+        }
+
+        if build.open.is_empty() {
+            let build = self.building.take().unwrap();
+            self.goal = Some(build.goal);
+            self.distances = build.distances;
+            self.age = 0;
+        }
+    }
+
+    // walking distance in tiles, None if the tile can't reach the goal or is too far out
+    pub fn distance_at(&self, tile: (i32, i32)) -> Option<f32> {
+        self.distances.get(&tile).copied()
+    }
+
+    // unit vector towards the neighbouring tile closest to the goal. None on the goal tile
+    // itself, where the goal is close enough to walk at directly, and off the field
+    pub fn direction_at(&self, position: Vector2f) -> Option<Vector2f> {
+        let tile = world_to_tile(position);
+        let distance = self.distance_at(tile)?;
+
+        if Some(tile) == self.goal {
+            return None;
+        }
+
+        // a neighbour that's in the field was reachable from here, so stepping to it is fine
+        // as long as it's the same kind of step the field was built with
+        let mut best: Option<((i32, i32), f32)> = None;
+        for direction in DIRECTIONS {
+            let next = (tile.0 + direction.0, tile.1 + direction.1);
+            let is_off_field = |x: i32, y: i32| !self.distances.contains_key(&(x, y));
+
+            if !can_step(tile, direction, &is_off_field) {
+                continue;
+            }
+
+            let next_distance = self.distances[&next];
+            if next_distance < distance && !matches!(best, Some((_, lowest)) if lowest <= next_distance) {
+                best = Some((next, next_distance));
+            }
+        }
+
+        let (next, _) = best?;
+        let target = Vector2f::new(
+            (next.0 * TILE_SIZE + TILE_SIZE / 2) as f32,
+            (next.1 * TILE_SIZE + TILE_SIZE / 2) as f32,
+        );
+
+        let delta = target - position;
+        let length = delta.length_sq().sqrt();
+        (length > 0.0).then(|| delta / length)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tile_center(tile_x: i32, tile_y: i32) -> Vector2f {
+        Vector2f::new((tile_x * TILE_SIZE + TILE_SIZE / 2) as f32, (tile_y * TILE_SIZE + TILE_SIZE / 2) as f32)
+    }
+
+    // a wall along x = 3 from y = -5 up to y = 5
+    fn is_blocked(tile_x: i32, tile_y: i32) -> bool {
+        tile_x == 3 && (-5..=5).contains(&tile_y)
+    }
+
+    fn build(field: &mut FlowField, goal: Vector2f) {
+        for _ in 0..500 {
+            field.update(goal, is_blocked);
+        }
+    }
+
+    #[test]
+    fn distances_grow_away_from_the_goal() {
+        let mut field = FlowField::new(DEFAULT_FLOW_FIELD_BUDGET);
+        build(&mut field, tile_center(0, 0));
+
+        assert_eq!(field.distance_at((0, 0)), Some(0.0));
+        assert_eq!(field.distance_at((-4, 0)), Some(4.0));
+        assert_eq!(field.distance_at((FLOW_FIELD_RADIUS + 1, 0)), None);
+        assert_eq!(field.distance_at((3, 0)), None);
+
+        // right behind the wall is a long way round
+        assert!(field.distance_at((4, 0)).unwrap() > 10.0);
+    }
+
+    #[test]
+    fn directions_lead_around_walls() {
+        let mut field = FlowField::new(DEFAULT_FLOW_FIELD_BUDGET);
+        build(&mut field, tile_center(0, 0));
+
+        // following the field from behind the wall gets to the goal without walking into it
+        let mut tile = (5, 0);
+        for _ in 0..40 {
+            if tile == (0, 0) {
+                break;
+            }
+
+            let direction = field.direction_at(tile_center(tile.0, tile.1)).unwrap();
+            tile = (tile.0 + direction.x.round() as i32, tile.1 + direction.y.round() as i32);
+            assert!(!is_blocked(tile.0, tile.1));
+        }
+
+        assert_eq!(tile, (0, 0));
+        assert_eq!(field.direction_at(tile_center(0, 0)), None);
+    }
+
+    #[test]
+    fn old_field_answers_while_the_new_one_builds() {
+        let mut field = FlowField::new(50);
+        build(&mut field, tile_center(0, 0));
+        let old_distance = field.distance_at((-4, 0));
+
+        // one tick isn't enough for the whole field at this budget
+        field.update(tile_center(-10, 0), is_blocked);
+        assert_eq!(field.distance_at((-4, 0)), old_distance);
+
+        build(&mut field, tile_center(-10, 0));
+        assert_eq!(field.distance_at((-4, 0)), Some(6.0));
+    }
+
+    #[test]
+    fn goals_that_never_sit_still_still_get_fields() {
+        let mut field = FlowField::new(DEFAULT_FLOW_FIELD_BUDGET);
+
+        // a new tile every tick, far quicker than a whole field gets built
+        for tick in 0..30 {
+            field.update(tile_center(-tick, 20), is_blocked);
+        }
+
+        let goal = field.goal.unwrap();
+        assert!(goal.0 < 0 && goal.1 == 20);
+        assert_eq!(field.distance_at(goal), Some(0.0));
+        assert!(field.direction_at(tile_center(goal.0 + 5, 20)).is_some());
+    }
+}
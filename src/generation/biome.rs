@@ -0,0 +1,118 @@
+use crate::{
+    entities::zombie::ZombieKind,
+    map::{DIRT, GRASS, SAND, STONE},
+    prop::PropKind,
+};
+
+// a region of the world with its own look and its own zombies. picked per chunk from the
+// temperature/moisture/elevation noise and blended into its neighbours at chunk borders
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Hash)]
+pub enum Biome {
+    FOREST = 0,
+    BEACH = 1,
+    PLAINS = 2,
+    QUARRY = 3,
+    TOWN_OUTSKIRTS = 4,
+}
+
+pub struct SpawnTable {
+    // average number of zombies a chunk of this biome holds
+    pub zombies_per_chunk: f32,
+    pub kinds: &'static [(ZombieKind, u32)],
+}
+
+impl Biome {
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(Biome::FOREST),
+            1 => Some(Biome::BEACH),
+            2 => Some(Biome::PLAINS),
+            3 => Some(Biome::QUARRY),
+            4 => Some(Biome::TOWN_OUTSKIRTS),
+            _ => None,
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Biome::FOREST => "forest",
+            Biome::BEACH => "beach",
+            Biome::PLAINS => "plains",
+            Biome::QUARRY => "quarry",
+            Biome::TOWN_OUTSKIRTS => "town outskirts",
+        }
+    }
+
+    // tiles with their weights. the first entry is the main tile of the biome
+    pub fn palette(&self) -> &'static [(u16, u32)] {
+        match self {
+            Biome::FOREST => &[(GRASS, 7), (DIRT, 3)],
+            Biome::BEACH => &[(SAND, 9), (GRASS, 1)],
+            Biome::PLAINS => &[(GRASS, 9), (DIRT, 1)],
+            Biome::QUARRY => &[(STONE, 6), (DIRT, 3), (SAND, 1)],
+            Biome::TOWN_OUTSKIRTS => &[(DIRT, 5), (GRASS, 4), (STONE, 1)],
+        }
+    }
+
+    // chance of any single tile getting a prop
+    pub fn prop_density(&self) -> f32 {
+        match self {
+            Biome::FOREST => 0.2,
+            Biome::BEACH => 0.01,
+            Biome::PLAINS => 0.03,
+            Biome::QUARRY => 0.06,
+            Biome::TOWN_OUTSKIRTS => 0.02,
+        }
+    }
+
+    pub fn props(&self) -> &'static [(PropKind, u32)] {
+        match self {
+            Biome::FOREST => &[(PropKind::TREE, 5), (PropKind::BUSH, 2)],
+            Biome::BEACH => &[(PropKind::ROCK, 1)],
+            Biome::PLAINS => &[(PropKind::BUSH, 3), (PropKind::TREE, 1)],
+            Biome::QUARRY => &[(PropKind::ROCK, 1)],
+            Biome::TOWN_OUTSKIRTS => &[(PropKind::BUSH, 2), (PropKind::TREE, 1)],
+        }
+    }
+
+    pub fn spawn_table(&self) -> SpawnTable {
+        match self {
+            Biome::FOREST => SpawnTable {
+                zombies_per_chunk: 0.3,
+                kinds: &[(ZombieKind::WALKER, 1)],
+            },
+            Biome::BEACH => SpawnTable {
+                zombies_per_chunk: 0.1,
+                kinds: &[(ZombieKind::WALKER, 1)],
+            },
+            Biome::PLAINS => SpawnTable {
+                zombies_per_chunk: 0.15,
+                kinds: &[(ZombieKind::WALKER, 4), (ZombieKind::RUNNER, 1)],
+            },
+            Biome::QUARRY => SpawnTable {
+                zombies_per_chunk: 0.2,
+                kinds: &[(ZombieKind::WALKER, 3), (ZombieKind::RUNNER, 1)],
+            },
+            Biome::TOWN_OUTSKIRTS => SpawnTable {
+                zombies_per_chunk: 0.6,
+                kinds: &[(ZombieKind::WALKER, 2), (ZombieKind::RUNNER, 1)],
+            },
+        }
+    }
+}
+
+// roll is 0.0..1.0, each entry gets a share of that range proportional to its weight
+pub fn pick_weighted<T: Copy>(entries: &[(T, u32)], roll: f64) -> T {
+    let total: u32 = entries.iter().map(|(_, weight)| weight).sum();
+    let mut target = roll.clamp(0.0, 1.0) * total as f64;
+
+    for (entry, weight) in entries {
+        if target < *weight as f64 {
+            return *entry;
+        }
+        target -= *weight as f64;
+    }
+
+    entries[entries.len() - 1].0
+}
@@ -0,0 +1,306 @@
+pub mod biome;
+pub mod noise;
+pub mod settlement;
+
+use crate::map::{CHUNK_SIZE, SAND, WATER};
+use biome::{pick_weighted, Biome};
+use noise::Noise;
+use settlement::{tile_to_cell, OUTSKIRTS_MARGIN};
+
+// how many tiles one unit of noise covers. bigger numbers mean bigger, smoother features
+const ELEVATION_SCALE: f64 = 64.0;
+const MOISTURE_SCALE: f64 = 96.0;
+const TEMPERATURE_SCALE: f64 = 160.0;
+// small patches of the biome's secondary tiles
+const DETAIL_SCALE: f64 = 6.0;
+// rivers follow the zero line of this noise, bigger means longer, lazier bends
+const RIVER_SCALE: f64 = 180.0;
+
+const ELEVATION_OCTAVES: u32 = 5;
+const MOISTURE_OCTAVES: u32 = 3;
+const TEMPERATURE_OCTAVES: u32 = 2;
+const DETAIL_OCTAVES: u32 = 2;
+const RIVER_OCTAVES: u32 = 3;
+
+// salts so the noise maps aren't just copies of the elevation map
+const MOISTURE_SALT: u64 = 0x6d6f_6973_7475_7265;
+const TEMPERATURE_SALT: u64 = 0x7465_6d70_6572_6174;
+const DETAIL_SALT: u64 = 0x6465_7461_696c_7321;
+const RIVER_SALT: u64 = 0x7269_7665_7262_6564;
+
+const CHUNK_SEED_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+const BLEND_SALT: u64 = 0x626c_656e_6469_6e67;
+
+// cutoffs for picking a chunk's biome
+const BEACH_BELOW: f64 = -0.2;
+const QUARRY_ABOVE: f64 = 0.25;
+const FOREST_ABOVE_MOISTURE: f64 = 0.0;
+const FOREST_BELOW_TEMPERATURE: f64 = 0.2;
+// tiles around a chunk border where the two biomes dither into each other, the rest of the
+// chunk is all its own biome
+const BLEND_WIDTH: f64 = 4.0;
+
+// the lowest ground floods, with a strip of sand around it. any water above this is a river
+pub const LAKE_BELOW: f64 = -0.32;
+const LAKE_SHORE_WIDTH: f64 = 0.03;
+// how close to the river noise's zero line is water, and how much further the sand bank goes
+const RIVER_WIDTH: f64 = 0.012;
+const RIVER_BANK_WIDTH: f64 = 0.01;
+
+// turns the world seed into tiles. everything works in world tile coordinates so a chunk
+// generated now, later, or on another thread always comes out the same.
+#[derive(Clone)]
+pub struct WorldGenerator {
+    pub seed: u32,
+    elevation: Noise,
+    moisture: Noise,
+    temperature: Noise,
+    detail: Noise,
+    river: Noise,
+}
+
+impl WorldGenerator {
+    pub fn new(seed: u32) -> Self {
+        WorldGenerator {
+            seed,
+            elevation: Noise::new(seed as u64),
+            moisture: Noise::new(seed as u64 ^ MOISTURE_SALT),
+            temperature: Noise::new(seed as u64 ^ TEMPERATURE_SALT),
+            detail: Noise::new(seed as u64 ^ DETAIL_SALT),
+            river: Noise::new(seed as u64 ^ RIVER_SALT),
+        }
+    }
+
+    // seed for anything random inside a single chunk. hashes the signed chunk coordinates
+    // together with the world seed so every chunk gets its own stream, including the ones
+    // on the axes and the ones mirrored across them
+    pub fn chunk_seed(&self, chunk_x: i32, chunk_y: i32) -> u64 {
+        self.cell_seed(chunk_x, chunk_y, CHUNK_SEED_SALT)
+    }
+
+    // same idea as chunk_seed for any other grid, the salt keeps the streams apart
+    pub fn cell_seed(&self, x: i32, y: i32, salt: u64) -> u64 {
+        let hash = mix(self.seed as u64 ^ salt);
+        let hash = mix(hash ^ x as u32 as u64);
+        mix(hash ^ y as u32 as u64)
+    }
+
+    // beaches, and anything lower
+    pub fn is_low_ground(&self, tile_x: i32, tile_y: i32) -> bool {
+        self.elevation(tile_x, tile_y) < BEACH_BELOW
+    }
+
+    pub fn elevation(&self, tile_x: i32, tile_y: i32) -> f64 {
+        self.elevation.fbm(
+            tile_x as f64 / ELEVATION_SCALE,
+            tile_y as f64 / ELEVATION_SCALE,
+            ELEVATION_OCTAVES,
+        )
+    }
+
+    pub fn moisture(&self, tile_x: i32, tile_y: i32) -> f64 {
+        self.moisture.fbm(
+            tile_x as f64 / MOISTURE_SCALE,
+            tile_y as f64 / MOISTURE_SCALE,
+            MOISTURE_OCTAVES,
+        )
+    }
+
+    pub fn temperature(&self, tile_x: i32, tile_y: i32) -> f64 {
+        self.temperature.fbm(
+            tile_x as f64 / TEMPERATURE_SCALE,
+            tile_y as f64 / TEMPERATURE_SCALE,
+            TEMPERATURE_OCTAVES,
+        )
+    }
+
+    // the biome a whole chunk belongs to, decided at its centre tile
+    pub fn chunk_biome(&self, chunk_x: i32, chunk_y: i32) -> Biome {
+        let tile_x = chunk_x * CHUNK_SIZE as i32 + CHUNK_SIZE as i32 / 2;
+        let tile_y = chunk_y * CHUNK_SIZE as i32 + CHUNK_SIZE as i32 / 2;
+
+        let elevation = self.elevation(tile_x, tile_y);
+
+        if elevation < BEACH_BELOW {
+            Biome::BEACH
+        } else if elevation > QUARRY_ABOVE {
+            Biome::QUARRY
+        } else if self.near_town(tile_x, tile_y) {
+            Biome::TOWN_OUTSKIRTS
+        } else if self.moisture(tile_x, tile_y) > FOREST_ABOVE_MOISTURE && self.temperature(tile_x, tile_y) < FOREST_BELOW_TEMPERATURE {
+            Biome::FOREST
+        } else {
+            Biome::PLAINS
+        }
+    }
+
+    fn near_town(&self, tile_x: i32, tile_y: i32) -> bool {
+        let (cell_x, cell_y) = tile_to_cell(tile_x, tile_y);
+
+        match self.town_site(cell_x, cell_y) {
+            Some(site) => {
+                let reach = site.radius + OUTSKIRTS_MARGIN;
+                (tile_x - site.center.0).abs() <= reach && (tile_y - site.center.1).abs() <= reach
+            },
+            None => false,
+        }
+    }
+
+    // biome of a single tile. the four nearest chunk centres are weighted by how close the tile
+    // is to their borders and one of their biomes is picked with a per-tile hash, so borders
+    // dither across BLEND_WIDTH tiles instead of cutting straight along chunk edges
+    pub fn biome_at(&self, tile_x: i32, tile_y: i32) -> Biome {
+        // chunk space with chunk centres on whole numbers
+        let chunk_x = (tile_x as f64 + 0.5) / CHUNK_SIZE as f64 - 0.5;
+        let chunk_y = (tile_y as f64 + 0.5) / CHUNK_SIZE as f64 - 0.5;
+
+        let left = chunk_x.floor();
+        let top = chunk_y.floor();
+        let blend_x = border_blend(chunk_x - left);
+        let blend_y = border_blend(chunk_y - top);
+
+        let corners = [
+            (left as i32, top as i32, (1.0 - blend_x) * (1.0 - blend_y)),
+            (left as i32 + 1, top as i32, blend_x * (1.0 - blend_y)),
+            (left as i32, top as i32 + 1, (1.0 - blend_x) * blend_y),
+            (left as i32 + 1, top as i32 + 1, blend_x * blend_y),
+        ];
+
+        let mut roll = self.tile_hash(tile_x, tile_y, BLEND_SALT);
+        for (corner_x, corner_y, weight) in corners {
+            if roll < weight {
+                return self.chunk_biome(corner_x, corner_y);
+            }
+            roll -= weight;
+        }
+
+        self.chunk_biome(corners[3].0, corners[3].1)
+    }
+
+    // ground tile from the biome's palette. the biome is passed in since callers usually
+    // need it for props too, and working it out means four chunk biome lookups
+    pub fn tile_at_in(&self, biome: Biome, tile_x: i32, tile_y: i32) -> u16 {
+        let detail = self.detail.fbm(tile_x as f64 / DETAIL_SCALE, tile_y as f64 / DETAIL_SCALE, DETAIL_OCTAVES);
+
+        // fbm bunches up around 0, stretch it out so the palette weights roughly hold
+        let roll = ((detail * 2.0).clamp(-1.0, 1.0) + 1.0) / 2.0;
+
+        pick_weighted(biome.palette(), roll)
+    }
+
+    // lakes, rivers and their sand banks, None everywhere else. rivers are the zero line of a
+    // noise map so they never end at a chunk border. they rise out of the quarry highlands and
+    // run until they reach a lake or leave the world
+    pub fn water_at(&self, tile_x: i32, tile_y: i32) -> Option<u16> {
+        let elevation = self.elevation(tile_x, tile_y);
+
+        if elevation < LAKE_BELOW {
+            return Some(WATER);
+        }
+        let lake_shore = elevation < LAKE_BELOW + LAKE_SHORE_WIDTH;
+
+        if elevation > QUARRY_ABOVE {
+            return None;
+        }
+
+        let river = self.river.fbm(tile_x as f64 / RIVER_SCALE, tile_y as f64 / RIVER_SCALE, RIVER_OCTAVES).abs();
+
+        if river < RIVER_WIDTH {
+            Some(WATER)
+        } else if river < RIVER_WIDTH + RIVER_BANK_WIDTH || lake_shore {
+            Some(SAND)
+        } else {
+            None
+        }
+    }
+
+    // 0.0..1.0, the same every time for the same tile and salt
+    pub fn tile_hash(&self, tile_x: i32, tile_y: i32, salt: u64) -> f64 {
+        let hash = self.cell_seed(tile_x, tile_y, salt);
+
+        (hash >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+// how far to lean towards the next chunk centre, from the fraction of the way there. only
+// leaves 0 or 1 inside the band around the border halfway between them
+fn border_blend(fraction: f64) -> f64 {
+    let band = BLEND_WIDTH / CHUNK_SIZE as f64;
+    ((fraction - 0.5) / band + 0.5).clamp(0.0, 1.0)
+}
+
+// splitmix64 finalizer, scrambles every input bit into every output bit
+fn mix(value: u64) -> u64 {
+    let value = (value ^ (value >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    let value = (value ^ (value >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    value ^ (value >> 31)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::*;
+
+    const TEST_SEED: u32 = 2024;
+
+    #[test]
+    fn chunk_biomes_only_depend_on_the_seed() {
+        let generator = WorldGenerator::new(TEST_SEED);
+        let again = WorldGenerator::new(TEST_SEED);
+        let other = WorldGenerator::new(TEST_SEED + 1);
+
+        let mut seen = HashSet::new();
+        let mut differs = false;
+        for chunk_x in -20..=20 {
+            for chunk_y in -20..=20 {
+                let biome = generator.chunk_biome(chunk_x, chunk_y);
+                assert_eq!(biome, again.chunk_biome(chunk_x, chunk_y));
+                differs |= biome != other.chunk_biome(chunk_x, chunk_y);
+                seen.insert(biome);
+            }
+        }
+
+        assert!(differs);
+        assert!(seen.len() >= 4, "only found {:?}", seen);
+    }
+
+    #[test]
+    fn biomes_only_dither_around_chunk_borders() {
+        let generator = WorldGenerator::new(TEST_SEED);
+        let size = CHUNK_SIZE as i32;
+        let band = BLEND_WIDTH as i32 / 2;
+
+        let mut borders = 0;
+        for chunk_x in -30..30 {
+            for chunk_y in -30..30 {
+                let biome = generator.chunk_biome(chunk_x, chunk_y);
+                let (left, top) = (chunk_x * size, chunk_y * size);
+
+                for tile_x in left + band..left + size - band {
+                    for tile_y in top + band..top + size - band {
+                        assert_eq!(generator.biome_at(tile_x, tile_y), biome);
+                    }
+                }
+
+                let neighbour = generator.chunk_biome(chunk_x + 1, chunk_y);
+                if neighbour == biome {
+                    continue;
+                }
+
+                // across the border to the right, away from the corners, it's a mix of the two
+                let border = left + size;
+                let mut found = HashSet::new();
+                for tile_x in border - band..border + band {
+                    for tile_y in top + band..top + size - band {
+                        found.insert(generator.biome_at(tile_x, tile_y));
+                    }
+                }
+                assert_eq!(found, HashSet::from([biome, neighbour]));
+                borders += 1;
+            }
+        }
+
+        assert!(borders > 0);
+    }
+}
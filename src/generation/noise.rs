@@ -0,0 +1,96 @@
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+
+// gradient directions used by the perlin noise, 8 evenly spaced unit vectors
+const GRADIENTS: [(f64, f64); 8] = [
+    (1.0, 0.0),
+    (-1.0, 0.0),
+    (0.0, 1.0),
+    (0.0, -1.0),
+    (std::f64::consts::FRAC_1_SQRT_2, std::f64::consts::FRAC_1_SQRT_2),
+    (-std::f64::consts::FRAC_1_SQRT_2, std::f64::consts::FRAC_1_SQRT_2),
+    (std::f64::consts::FRAC_1_SQRT_2, -std::f64::consts::FRAC_1_SQRT_2),
+    (-std::f64::consts::FRAC_1_SQRT_2, -std::f64::consts::FRAC_1_SQRT_2),
+];
+
+// 2D perlin noise. the permutation table only depends on the seed, so sampling the same
+// world position always gives the same value no matter which chunk asks for it.
+#[derive(Clone)]
+pub struct Noise {
+    permutation: [u8; 512],
+}
+
+impl Noise {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        let mut table: Vec<u8> = (0..=255).collect();
+        table.shuffle(&mut rng);
+
+        let mut permutation = [0; 512];
+        for (index, value) in permutation.iter_mut().enumerate() {
+            *value = table[index & 255];
+        }
+
+        Noise { permutation }
+    }
+
+    // single octave of noise, roughly in the range -1.0..=1.0
+    pub fn sample(&self, x: f64, y: f64) -> f64 {
+        let cell_x = x.floor();
+        let cell_y = y.floor();
+
+        let local_x = x - cell_x;
+        let local_y = y - cell_y;
+
+        let xi = (cell_x as i64 & 255) as usize;
+        let yi = (cell_y as i64 & 255) as usize;
+
+        let top_left = self.gradient(xi, yi, local_x, local_y);
+        let top_right = self.gradient(xi + 1, yi, local_x - 1.0, local_y);
+        let bottom_left = self.gradient(xi, yi + 1, local_x, local_y - 1.0);
+        let bottom_right = self.gradient(xi + 1, yi + 1, local_x - 1.0, local_y - 1.0);
+
+        let fade_x = fade(local_x);
+        let fade_y = fade(local_y);
+
+        let top = lerp(top_left, top_right, fade_x);
+        let bottom = lerp(bottom_left, bottom_right, fade_x);
+
+        lerp(top, bottom, fade_y) * std::f64::consts::SQRT_2
+    }
+
+    // fractal brownian motion: several octaves layered on top of each other, each one with
+    // double the frequency and half the amplitude of the last. normalized back to -1.0..=1.0
+    pub fn fbm(&self, x: f64, y: f64, octaves: u32) -> f64 {
+        let mut total = 0.0;
+        let mut frequency = 1.0;
+        let mut amplitude = 1.0;
+        let mut max_amplitude = 0.0;
+
+        for octave in 0..octaves {
+            // offset every octave so the lattice points don't line up
+            let offset = octave as f64 * 31.7;
+            total += self.sample(x * frequency + offset, y * frequency + offset) * amplitude;
+            max_amplitude += amplitude;
+            frequency *= 2.0;
+            amplitude *= 0.5;
+        }
+
+        (total / max_amplitude).clamp(-1.0, 1.0)
+    }
+
+    fn gradient(&self, xi: usize, yi: usize, x: f64, y: f64) -> f64 {
+        let hash = self.permutation[self.permutation[xi] as usize + yi];
+        let (gradient_x, gradient_y) = GRADIENTS[hash as usize & 7];
+
+        gradient_x * x + gradient_y * y
+    }
+}
+
+fn fade(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f64, b: f64, t: f64) -> f64 {
+    a + (b - a) * t
+}
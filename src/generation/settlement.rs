@@ -0,0 +1,386 @@
+use rand::{rngs::StdRng, Rng, SeedableRng};
+
+use crate::map::{ASPHALT, CHUNK_SIZE, DOOR, FLOOR, ROOF, WALL, WATER};
+use super::WorldGenerator;
+
+// the world is split into square cells, each one holds at most one town. roads only run
+// between towns in neighbouring cells, so a chunk never has to look further than the cells
+// around its own
+pub const TOWN_CELL_SIZE: i32 = 160;
+
+// odds of a cell getting a town at all
+const TOWN_CHANCE: f64 = 0.6;
+const MIN_TOWN_RADIUS: i32 = 16;
+const MAX_TOWN_RADIUS: i32 = 28;
+// keeps towns (and their outskirts) away from the cell edges
+const TOWN_MARGIN: i32 = MAX_TOWN_RADIUS + 20;
+// how far past the town radius the outskirts biome reaches
+pub const OUTSKIRTS_MARGIN: i32 = 16;
+
+// tiles either side of a road's centre line
+const ROAD_HALF_WIDTH: i32 = 1;
+
+const BUILDING_ATTEMPTS: u32 = 40;
+const MIN_BUILDING_SIZE: i32 = 4;
+const MAX_BUILDING_SIZE: i32 = 9;
+
+const TOWN_SALT: u64 = 0x746f_776e_7369_7465;
+const TOWN_POSITION_SALT: u64 = 0x746f_776e_706f_7369;
+const ROAD_SALT: u64 = 0x726f_6164_636f_726e;
+
+// where a town goes, cheap enough to ask for on every tile
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct TownSite {
+    pub cell: (i32, i32),
+    pub center: (i32, i32),
+    pub radius: i32,
+}
+
+// rect in world tiles, the door sits on the wall facing the nearest street
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Building {
+    pub left: i32,
+    pub top: i32,
+    pub width: i32,
+    pub height: i32,
+    pub door: (i32, i32),
+}
+
+impl Building {
+    pub fn contains(&self, tile_x: i32, tile_y: i32) -> bool {
+        tile_x >= self.left && tile_x < self.left + self.width && tile_y >= self.top && tile_y < self.top + self.height
+    }
+
+    // the outermost ring of tiles, doors included
+    pub fn is_wall(&self, tile_x: i32, tile_y: i32) -> bool {
+        self.contains(tile_x, tile_y)
+            && (tile_x == self.left || tile_x == self.left + self.width - 1 || tile_y == self.top || tile_y == self.top + self.height - 1)
+    }
+
+    fn overlaps(&self, other: &Building, gap: i32) -> bool {
+        self.left - gap < other.left + other.width
+            && other.left - gap < self.left + self.width
+            && self.top - gap < other.top + other.height
+            && other.top - gap < self.top + self.height
+    }
+}
+
+pub struct Town {
+    pub site: TownSite,
+    pub buildings: Vec<Building>,
+}
+
+// an L shaped road, from -> corner is straight along one axis and corner -> to along the other
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Road {
+    pub from: (i32, i32),
+    pub corner: (i32, i32),
+    pub to: (i32, i32),
+}
+
+impl Road {
+    pub fn contains(&self, tile_x: i32, tile_y: i32) -> bool {
+        on_segment(self.from, self.corner, tile_x, tile_y) || on_segment(self.corner, self.to, tile_x, tile_y)
+    }
+}
+
+// axis aligned segment widened by the road width
+fn on_segment(start: (i32, i32), end: (i32, i32), tile_x: i32, tile_y: i32) -> bool {
+    let (left, right) = (start.0.min(end.0), start.0.max(end.0));
+    let (top, bottom) = (start.1.min(end.1), start.1.max(end.1));
+
+    tile_x >= left - ROAD_HALF_WIDTH
+        && tile_x <= right + ROAD_HALF_WIDTH
+        && tile_y >= top - ROAD_HALF_WIDTH
+        && tile_y <= bottom + ROAD_HALF_WIDTH
+}
+
+// everything built that can touch one chunk
+pub struct Structures {
+    pub towns: Vec<Town>,
+    pub roads: Vec<Road>,
+}
+
+impl Structures {
+    pub fn tile_at(&self, tile_x: i32, tile_y: i32) -> Option<u16> {
+        let in_building = self.buildings().any(|building| building.contains(tile_x, tile_y));
+
+        if in_building {
+            Some(FLOOR)
+        } else if self.roads.iter().any(|road| road.contains(tile_x, tile_y)) {
+            Some(ASPHALT)
+        } else {
+            None
+        }
+    }
+}
+
+impl Structures {
+    fn buildings(&self) -> impl Iterator<Item = &Building> {
+        self.towns.iter().flat_map(|town| town.buildings.iter())
+    }
+
+    // wall/object layer, walls around every building with a closed door in them
+    pub fn wall_at(&self, tile_x: i32, tile_y: i32) -> Option<u16> {
+        let building = self.buildings().find(|building| building.is_wall(tile_x, tile_y))?;
+
+        if building.door == (tile_x, tile_y) {
+            Some(DOOR)
+        } else {
+            Some(WALL)
+        }
+    }
+
+    pub fn roof_at(&self, tile_x: i32, tile_y: i32) -> Option<u16> {
+        self.buildings()
+            .any(|building| building.contains(tile_x, tile_y))
+            .then_some(ROOF)
+    }
+}
+
+pub fn tile_to_cell(tile_x: i32, tile_y: i32) -> (i32, i32) {
+    (tile_x.div_euclid(TOWN_CELL_SIZE), tile_y.div_euclid(TOWN_CELL_SIZE))
+}
+
+impl WorldGenerator {
+    // only depends on the seed and the cell, so every chunk agrees on where towns are no
+    // matter which order they're generated in
+    pub fn town_site(&self, cell_x: i32, cell_y: i32) -> Option<TownSite> {
+        if self.tile_hash(cell_x, cell_y, TOWN_SALT) >= TOWN_CHANCE {
+            return None;
+        }
+
+        let mut rng = StdRng::seed_from_u64(self.cell_seed(cell_x, cell_y, TOWN_POSITION_SALT));
+
+        let center = (
+            cell_x * TOWN_CELL_SIZE + rng.gen_range(TOWN_MARGIN..TOWN_CELL_SIZE - TOWN_MARGIN),
+            cell_y * TOWN_CELL_SIZE + rng.gen_range(TOWN_MARGIN..TOWN_CELL_SIZE - TOWN_MARGIN),
+        );
+
+        // nobody builds on the beach
+        if self.is_low_ground(center.0, center.1) {
+            return None;
+        }
+
+        Some(TownSite {
+            cell: (cell_x, cell_y),
+            center,
+            radius: rng.gen_range(MIN_TOWN_RADIUS..=MAX_TOWN_RADIUS),
+        })
+    }
+
+    pub fn town(&self, cell_x: i32, cell_y: i32) -> Option<Town> {
+        let site = self.town_site(cell_x, cell_y)?;
+        let mut rng = StdRng::seed_from_u64(self.cell_seed(cell_x, cell_y, TOWN_SALT));
+
+        let mut buildings: Vec<Building> = Vec::new();
+
+        for _ in 0..BUILDING_ATTEMPTS {
+            let width = rng.gen_range(MIN_BUILDING_SIZE..=MAX_BUILDING_SIZE);
+            let height = rng.gen_range(MIN_BUILDING_SIZE..=MAX_BUILDING_SIZE);
+            let left = site.center.0 + rng.gen_range(-site.radius..=site.radius - width);
+            let top = site.center.1 + rng.gen_range(-site.radius..=site.radius - height);
+
+            // the main streets run through the centre in both directions, keep a tile of
+            // pavement between them and the walls
+            let street_gap = ROAD_HALF_WIDTH + 1;
+            let crosses_vertical_street = left - street_gap <= site.center.0 && left + width + street_gap > site.center.0;
+            let crosses_horizontal_street = top - street_gap <= site.center.1 && top + height + street_gap > site.center.1;
+            if crosses_vertical_street || crosses_horizontal_street {
+                continue;
+            }
+
+            // door in the middle of the wall closest to a street
+            let distance_to_vertical = (left + width / 2 - site.center.0).abs();
+            let distance_to_horizontal = (top + height / 2 - site.center.1).abs();
+            let door = if distance_to_vertical < distance_to_horizontal {
+                let door_x = if left > site.center.0 { left } else { left + width - 1 };
+                (door_x, top + height / 2)
+            } else {
+                let door_y = if top > site.center.1 { top } else { top + height - 1 };
+                (left + width / 2, door_y)
+            };
+
+            let building = Building { left, top, width, height, door };
+
+            if buildings.iter().all(|other| !building.overlaps(other, 2)) && !self.is_flooded(&building) {
+                buildings.push(building);
+            }
+        }
+
+        Some(Town { site, buildings })
+    }
+
+    // nobody builds in a lake or a river. roads are left alone, they cross water as bridges
+    fn is_flooded(&self, building: &Building) -> bool {
+        (building.left..building.left + building.width)
+            .any(|tile_x| (building.top..building.top + building.height).any(|tile_y| self.water_at(tile_x, tile_y) == Some(WATER)))
+    }
+
+    // road between the towns of two neighbouring cells, if both have one
+    pub fn road_between(&self, first: (i32, i32), second: (i32, i32)) -> Option<Road> {
+        let from = self.town_site(first.0, first.1)?.center;
+        let to = self.town_site(second.0, second.1)?.center;
+
+        let corner = if self.tile_hash(first.0 ^ second.1, first.1 ^ second.0, ROAD_SALT) < 0.5 {
+            (to.0, from.1)
+        } else {
+            (from.0, to.1)
+        };
+
+        Some(Road { from, corner, to })
+    }
+
+    // towns in the 3x3 cells around the chunk plus every road between them. roads never leave
+    // the two cells they connect, so nothing outside of this can reach the chunk
+    pub fn structures_near_chunk(&self, chunk_x: i32, chunk_y: i32) -> Structures {
+        let (cell_x, cell_y) = tile_to_cell(chunk_x * CHUNK_SIZE as i32, chunk_y * CHUNK_SIZE as i32);
+
+        let mut towns = Vec::new();
+        let mut roads = Vec::new();
+
+        for x in cell_x - 1..=cell_x + 1 {
+            for y in cell_y - 1..=cell_y + 1 {
+                towns.extend(self.town(x, y));
+
+                if x < cell_x + 1 {
+                    roads.extend(self.road_between((x, y), (x + 1, y)));
+                }
+                if y < cell_y + 1 {
+                    roads.extend(self.road_between((x, y), (x, y + 1)));
+                }
+            }
+        }
+
+        // each town's own main streets
+        for town in towns.iter() {
+            let (center_x, center_y) = town.site.center;
+            let radius = town.site.radius;
+
+            roads.push(Road {
+                from: (center_x - radius, center_y),
+                corner: (center_x, center_y),
+                to: (center_x + radius, center_y),
+            });
+            roads.push(Road {
+                from: (center_x, center_y - radius),
+                corner: (center_x, center_y),
+                to: (center_x, center_y + radius),
+            });
+        }
+
+        Structures { towns, roads }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TEST_SEED: u32 = 123456789;
+
+    #[test]
+    fn towns_are_deterministic() {
+        let generator = WorldGenerator::new(TEST_SEED);
+        let other = WorldGenerator::new(TEST_SEED);
+
+        for cell_x in -3..=3 {
+            for cell_y in -3..=3 {
+                let first = generator.town(cell_x, cell_y);
+                let second = other.town(cell_x, cell_y);
+
+                assert_eq!(first.is_some(), second.is_some());
+                if let (Some(first), Some(second)) = (first, second) {
+                    assert_eq!(first.site, second.site);
+                    assert_eq!(first.buildings, second.buildings);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn buildings_stay_off_the_streets() {
+        let generator = WorldGenerator::new(TEST_SEED);
+
+        let towns: Vec<Town> = (-4..=4)
+            .flat_map(|cell_x| (-4..=4).map(move |cell_y| (cell_x, cell_y)))
+            .filter_map(|(cell_x, cell_y)| generator.town(cell_x, cell_y))
+            .collect();
+        assert!(!towns.is_empty());
+
+        for town in towns.iter() {
+            let (center_x, center_y) = town.site.center;
+
+            for building in town.buildings.iter() {
+                for offset in -ROAD_HALF_WIDTH..=ROAD_HALF_WIDTH {
+                    assert!(!building.contains(center_x + offset, building.top));
+                    assert!(!building.contains(building.left, center_y + offset));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn buildings_stay_out_of_the_water() {
+        let generator = WorldGenerator::new(TEST_SEED);
+
+        for cell_x in -6..=6 {
+            for cell_y in -6..=6 {
+                for building in generator.town(cell_x, cell_y).iter().flat_map(|town| town.buildings.iter()) {
+                    for tile_x in building.left..building.left + building.width {
+                        for tile_y in building.top..building.top + building.height {
+                            assert_ne!(generator.water_at(tile_x, tile_y), Some(WATER), "{:?} is in the water", building);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn buildings_are_walled_with_one_door() {
+        let generator = WorldGenerator::new(TEST_SEED);
+        let town = (-4..=4)
+            .flat_map(|cell_x| (-4..=4).map(move |cell_y| (cell_x, cell_y)))
+            .find_map(|(cell_x, cell_y)| generator.town(cell_x, cell_y))
+            .unwrap();
+
+        let structures = Structures { towns: vec![town], roads: Vec::new() };
+
+        for building in structures.towns[0].buildings.iter() {
+            let mut doors = 0;
+
+            for tile_x in building.left..building.left + building.width {
+                for tile_y in building.top..building.top + building.height {
+                    assert_eq!(structures.roof_at(tile_x, tile_y), Some(ROOF));
+
+                    match structures.wall_at(tile_x, tile_y) {
+                        Some(DOOR) => doors += 1,
+                        Some(WALL) => assert!(building.is_wall(tile_x, tile_y)),
+                        // the inside stays open
+                        _ => assert!(!building.is_wall(tile_x, tile_y)),
+                    }
+                }
+            }
+
+            assert_eq!(doors, 1);
+            assert_eq!(structures.roof_at(building.left - 1, building.top), None);
+        }
+    }
+
+    #[test]
+    fn neighbouring_chunks_agree_on_structures() {
+        let generator = WorldGenerator::new(TEST_SEED);
+
+        // two chunks on either side of a cell border have to see the same road tiles there
+        let border_chunk = TOWN_CELL_SIZE / CHUNK_SIZE as i32;
+        let left = generator.structures_near_chunk(border_chunk - 1, 3);
+        let right = generator.structures_near_chunk(border_chunk, 3);
+
+        for tile_y in 0..TOWN_CELL_SIZE * 2 {
+            for tile_x in [TOWN_CELL_SIZE - 1, TOWN_CELL_SIZE] {
+                assert_eq!(left.tile_at(tile_x, tile_y), right.tile_at(tile_x, tile_y));
+            }
+        }
+    }
+}
@@ -0,0 +1,44 @@
+// anything that holds drinking water. filled up at lakes and rivers
+#[derive(Clone, PartialEq, Debug)]
+pub struct DrinkingContainer {
+    pub name: &'static str,
+    // litres
+    pub capacity: f32,
+    pub amount: f32,
+}
+
+impl DrinkingContainer {
+    pub fn new(name: &'static str, capacity: f32) -> Self {
+        DrinkingContainer {
+            name,
+            capacity,
+            amount: 0.0,
+        }
+    }
+
+    pub fn water_bottle() -> Self {
+        DrinkingContainer::new("water bottle", 1.0)
+    }
+
+    pub fn refill(&mut self) {
+        self.amount = self.capacity;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn containers_refill_to_capacity() {
+        let mut bottle = DrinkingContainer::water_bottle();
+        assert_eq!(bottle.amount, 0.0);
+
+        bottle.refill();
+        assert_eq!(bottle.amount, bottle.capacity);
+
+        bottle.amount = 0.3;
+        bottle.refill();
+        assert_eq!(bottle.amount, bottle.capacity);
+    }
+}
@@ -1,10 +1,24 @@
 mod assets;
+mod ballistics;
+mod chunk_cache;
+mod collision;
 mod entities;
+mod flow_field;
+mod generation;
+mod item;
 mod map;
+mod melee;
+mod pathfinding;
+mod prop;
+mod sound;
+mod spatial_hash;
+mod spawn_director;
 mod states;
+mod tile_registry;
 mod util;
+mod weapon;
 
-use std::time::Instant;
+use std::{sync::Arc, time::Instant};
 
 use sfml::{graphics::{Color, RenderTarget, RenderWindow}, system::Vector2f, window::{ContextSettings, Style, VideoMode}};
 
@@ -23,7 +37,7 @@ pub fn main() {
         &ContextSettings::default()
     );
     
-    let assets = Assets::new();
+    let assets = Arc::new(Assets::new());
 
     let mut states: Vec<Box<dyn State>> = vec![Box::new(GameState::new(
         assets.clone(), 
@@ -45,9 +59,17 @@ pub fn main() {
                 sfml::window::Event::KeyPressed { code, .. } => states.first_mut().unwrap().keypress_event(code),
                 sfml::window::Event::KeyReleased { code, .. } => states.first_mut().unwrap().keyrelease_event(code),
                 sfml::window::Event::MouseWheelScrolled { wheel, delta, x, y } => {},
-                sfml::window::Event::MouseButtonPressed { button, x, y } => {},
-                sfml::window::Event::MouseButtonReleased { button, x, y } => {},
-                sfml::window::Event::MouseMoved { x, y } => {},
+                sfml::window::Event::MouseButtonPressed { button, x, y } => {
+                    let state = states.first_mut().unwrap();
+                    state.mouse_position_event(Vector2f::new(x as f32, y as f32));
+                    state.mouse_press_event(button);
+                },
+                sfml::window::Event::MouseButtonReleased { button, x, y } => {
+                    let state = states.first_mut().unwrap();
+                    state.mouse_position_event(Vector2f::new(x as f32, y as f32));
+                    state.mouse_release_event(button);
+                },
+                sfml::window::Event::MouseMoved { x, y } => states.first_mut().unwrap().mouse_position_event(Vector2f::new(x as f32, y as f32)),
                 sfml::window::Event::MouseEntered => {},
                 sfml::window::Event::MouseLeft => {},
                 _ => {},
@@ -57,10 +79,12 @@ pub fn main() {
         // update
         if tick_time.elapsed().as_millis() >= 10 {
             tick_time = Instant::now();
-            states
-                .first_mut()
-                .expect("No states!")
-                .update();
+            let state = states.first_mut().expect("No states!");
+            state.update();
+
+            if let Some(next_state) = state.next_state() {
+                states[0] = next_state;
+            }
         }
 
         // render
@@ -1,27 +1,95 @@
-use std::{sync::{mpsc::{self, Receiver, Sender}, Arc}, thread::{self, JoinHandle}};
+use std::{collections::{HashMap, HashSet, VecDeque}, fs, io, path::{Path, PathBuf}, sync::{mpsc::{self, Receiver, Sender}, Arc}, thread::{self, JoinHandle}};
 
 use rand::{rngs::StdRng, Rng, SeedableRng};
-use sfml::{graphics::{FloatRect, IntRect, Rect, RenderTarget, RenderWindow, Sprite, Transformable}, system::Vector2f};
+use sfml::{graphics::{CircleShape, FloatRect, PrimitiveType, RenderStates, RenderTarget, RenderWindow, Shape, Transformable, Vertex}, system::Vector2f};
 
-use crate::{assets::Assets, entities::entity::Entity};
+use crate::{
+    assets::Assets,
+    ballistics::ray_box,
+    chunk_cache::{ChunkCache, DEFAULT_CACHE_BUDGET},
+    entities::entity::Entity,
+    generation::{biome::{pick_weighted, Biome}, WorldGenerator},
+    prop::{Prop, PropKind},
+    tile_registry::TileRegistry,
+};
 
 pub const TILE_SIZE: i32 = 32;
 pub const CHUNK_SIZE: usize = 8;
 pub const CHUNK_SIZE_PIXELS: usize = CHUNK_SIZE * TILE_SIZE as usize;
 
-// source Image objects, containing the image size and pointing to where the texture exists on the spritesheet
-pub const GRASS_IMG: IntRect = Rect::new(TILE_SIZE * 0, TILE_SIZE * 0, TILE_SIZE * 1, TILE_SIZE * 1);
-pub const SAND_IMG: IntRect  = Rect::new(TILE_SIZE * 1, TILE_SIZE * 0, TILE_SIZE * 1, TILE_SIZE * 1);
-pub const DIRT_IMG: IntRect  = Rect::new(TILE_SIZE * 0, TILE_SIZE * 1, TILE_SIZE * 1, TILE_SIZE * 1);
-pub const STONE_IMG: IntRect = Rect::new(TILE_SIZE * 1, TILE_SIZE * 1, TILE_SIZE * 1, TILE_SIZE * 1);
+// IDs of the tiles world generation places. everything else about a tile (texture, walkability,
+// speed...) lives in res/tiles.toml, see TileRegistry
+pub const GRASS: u16 = 0;
+pub const SAND: u16 = 1;
+pub const DIRT: u16 = 2;
+pub const STONE: u16 = 3;
+pub const ASPHALT: u16 = 4;
+pub const FLOOR: u16 = 5;
+pub const WALL: u16 = 6;
+pub const DOOR: u16 = 7;
+pub const DOOR_OPEN: u16 = 8;
+pub const ROOF: u16 = 9;
+pub const WATER: u16 = 10;
+
+// nothing on this layer, only used by the wall and roof layers
+pub const EMPTY: u16 = u16::MAX;
+
+// how many chunks around the camera are kept loaded. has to cover the corners of the screen
+pub const DEFAULT_LOAD_RADIUS: i32 = 4;
+// chunks further out than this get moved into the chunk cache. a bit bigger than the load
+// radius so walking back and forth over a chunk border doesn't load/unload every frame
+pub const DEFAULT_UNLOAD_RADIUS: i32 = 6;
+
+// modified chunks that fall out of the cache get written here, one file per chunk
+pub const SAVE_DIRECTORY: &str = "saves";
+const CHUNK_FILE_VERSION: u8 = 3;
+
+// how much of a roof's opacity goes away (or comes back) per frame
+const ROOF_FADE_STEP: f32 = 0.08;
+// stops the roof flood fill from running away if a roof is ever bigger than a building
+const MAX_ROOF_TILES: usize = 512;
+// how lit it is under a roof, outside is always 1
+const INDOOR_LIGHT: f32 = 0.35;
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Layer {
+    GROUND = 0,
+    // walls, doors and anything else standing on the ground
+    WALLS = 1,
+    // drawn above entities, hidden while the player is under it
+    ROOF = 2,
+}
+
+pub enum ChunkRequest {
+    Load(Vec<(i32, i32)>),
+    Save(Vec<Chunk>),
+    Shutdown,
+}
 
 pub struct Map {
-    pub chunks: Vec<Chunk>,
+    pub chunks: HashMap<(i32, i32), Chunk>,
     pub entities: Vec<Box<dyn Entity>>,
     pub assets: Arc<Assets>,
+    // same generator the worker thread uses, for asking about biomes and terrain directly
+    pub generator: WorldGenerator,
+
+    // radius in chunks around the camera centre that gets streamed in
+    pub load_radius: i32,
+    pub unload_radius: i32,
+    // unloaded chunks, kept around in memory until the budget runs out
+    pub cache: ChunkCache,
+    // chunks that have been requested from the generator but haven't come back yet
+    pending_chunks: HashSet<(i32, i32)>,
 
-    chunk_generator: JoinHandle<()>,
-    chunk_generator_sender: Sender<Vec<(i32, i32)>>,
+    // roof tiles of the building the player is in, in world tiles. they stay here while the
+    // roof fades back in after the player leaves
+    hidden_roof: HashSet<(i32, i32)>,
+    // opacity of the hidden roof, 0 is fully faded out
+    roof_alpha: f32,
+    player_tile: Option<(i32, i32)>,
+
+    chunk_generator: Option<JoinHandle<()>>,
+    chunk_generator_sender: Sender<ChunkRequest>,
     generated_chunks_receiver: Receiver<Vec<Chunk>>,
 }
 
@@ -32,93 +100,584 @@ impl Map {
         
         println!("{}", seed);
 
-        // TODO: this should cause tile generation to be "random". methodical generation can be added later so it isn't just noisy bullshit. 
-
-        let mut chunks = Vec::new();
-
-        for x in 0..16 {
-            for y in 0..16 {
-                chunks.push(Chunk::random(x * CHUNK_SIZE_PIXELS as i32, y * CHUNK_SIZE_PIXELS as i32, seed));
-            }
-        }
+        let generator = WorldGenerator::new(seed);
+        let save_directory = Path::new(SAVE_DIRECTORY).join(seed.to_string());
 
-        let (chunk_generator_sender, chunk_generator_receiver) = mpsc::channel::<Vec<(i32, i32)>>();
+        let (chunk_generator_sender, chunk_generator_receiver) = mpsc::channel::<ChunkRequest>();
         let (generated_chunks_sender, generated_chunks_receiver) = mpsc::channel::<Vec<Chunk>>();
 
+        // requests and results are in chunk coordinates. saved chunks are read back from disk,
+        // everything else is generated. all disk access happens here so a frame never waits on it
+        let worker_generator = generator.clone();
         let chunk_generator = thread::spawn(move || {
-            loop {
-                let chunk_request = chunk_generator_receiver.recv().expect("Channel closed");
-                let chunks: Vec<Chunk> = chunk_request
-                    .iter()
-                    .map(|chunk| {
-                        Chunk::random(chunk.0, chunk.1, seed)
-                    }).collect();
+            let generator = worker_generator;
+
+            while let Ok(chunk_request) = chunk_generator_receiver.recv() {
+                match chunk_request {
+                    ChunkRequest::Load(positions) => {
+                        let chunks: Vec<Chunk> = positions
+                            .iter()
+                            .map(|chunk| {
+                                Chunk::load(&save_directory, *chunk).unwrap_or_else(|| {
+                                    Chunk::generate(chunk.0 * CHUNK_SIZE_PIXELS as i32, chunk.1 * CHUNK_SIZE_PIXELS as i32, &generator)
+                                })
+                            }).collect();
 
-                generated_chunks_sender.send(chunks).expect("Channel closed");
+                        if generated_chunks_sender.send(chunks).is_err() {
+                            break;
+                        }
+                    },
+                    ChunkRequest::Save(chunks) => {
+                        for chunk in chunks {
+                            if let Err(error) = chunk.save(&save_directory) {
+                                println!("Failed to save chunk {:?}: {}", chunk.chunk_position(), error);
+                            }
+                        }
+                    },
+                    ChunkRequest::Shutdown => break,
+                }
             }
         });
 
         Map {
-            chunks,
+            chunks: HashMap::new(),
             entities: Vec::new(),
             assets,
+            generator,
 
-            chunk_generator,
+            load_radius: DEFAULT_LOAD_RADIUS,
+            unload_radius: DEFAULT_UNLOAD_RADIUS,
+            cache: ChunkCache::new(DEFAULT_CACHE_BUDGET),
+            pending_chunks: HashSet::new(),
+
+            hidden_roof: HashSet::new(),
+            roof_alpha: 1.0,
+            player_tile: None,
+
+            chunk_generator: Some(chunk_generator),
             chunk_generator_sender,
             generated_chunks_receiver
         }
     }
     
-    pub fn render(&self, window: &mut RenderWindow, camera_offset: Vector2f, display_size: Vector2f) {
-        
-        let mut sprite = Sprite::new();
-        sprite.set_texture(&self.assets.terrain_texture, true);
-
-        for chunk in self.chunks.iter() {
-            let chunk_bounds = FloatRect::new(
-                chunk.x as f32,
-                chunk.y as f32,
-                (chunk.x + CHUNK_SIZE_PIXELS as i32) as f32,
-                (chunk.y + CHUNK_SIZE_PIXELS as i32) as f32
+    // streams chunks in around the camera. never blocks: finished chunks are picked up on
+    // whichever frame they arrive, missing ones are handed to the generator thread
+    pub fn update(&mut self, camera_offset: Vector2f, display_size: Vector2f) {
+        while let Ok(chunks) = self.generated_chunks_receiver.try_recv() {
+            for chunk in chunks {
+                let position = chunk.chunk_position();
+                self.pending_chunks.remove(&position);
+                self.chunks.insert(position, chunk);
+            }
+        }
+
+        let center = world_to_chunk(camera_offset + display_size / 2.0);
+
+        // anything past the unload radius goes to the cache, whatever the cache drops is
+        // written to disk if it has been changed since it was generated
+        let unload_radius = self.unload_radius.max(self.load_radius);
+        let far_chunks: Vec<(i32, i32)> = self.chunks
+            .keys()
+            .filter(|(x, y)| (x - center.0).pow(2) + (y - center.1).pow(2) > unload_radius.pow(2))
+            .copied()
+            .collect();
+
+        let mut modified_chunks = Vec::new();
+        for position in far_chunks {
+            let chunk = self.chunks.remove(&position).unwrap();
+            modified_chunks.extend(
+                self.cache
+                    .insert(chunk)
+                    .into_iter()
+                    .filter(|chunk| chunk.modified)
             );
+        }
 
-            if FloatRect::from_vecs(camera_offset, display_size)
-                .intersection(&chunk_bounds)
-                .is_some() {
-                
-                for tile_y in 0..chunk.tiles.len() {
-                    for tile_x in 0..chunk.tiles[tile_y].len() {
-
-                        sprite.set_texture_rect(match chunk.tiles[tile_x][tile_y] {
-                            0 => GRASS_IMG,
-                            1 => SAND_IMG,
-                            2 => DIRT_IMG,
-                            3 => STONE_IMG,
-                            _ => GRASS_IMG,
-                        });
-
-                        sprite.set_position(
-                            Vector2f::new(
-                                (chunk.x + (tile_x as i32 * TILE_SIZE)) as f32, 
-                                (chunk.y + (tile_y as i32 * TILE_SIZE)) as f32
-                            ) - camera_offset
-                        );
-        
-                        window.draw(&sprite);
+        if !modified_chunks.is_empty() {
+            self.chunk_generator_sender.send(ChunkRequest::Save(modified_chunks)).expect("Chunk generator stopped");
+        }
+
+        let mut missing = Vec::new();
+        for position in chunks_in_radius(center, self.load_radius) {
+            if self.chunks.contains_key(&position) || self.pending_chunks.contains(&position) {
+                continue;
+            }
+
+            match self.cache.take(position) {
+                Some(chunk) => { self.chunks.insert(position, chunk); },
+                None => missing.push(position),
+            }
+        }
+
+        if !missing.is_empty() {
+            self.pending_chunks.extend(missing.iter().copied());
+            self.chunk_generator_sender.send(ChunkRequest::Load(missing)).expect("Chunk generator stopped");
+        }
+
+        for chunk in self.chunks.values_mut().filter(|chunk| chunk.vertices_dirty) {
+            chunk.build_vertices(&self.assets.tiles);
+        }
+    }
+
+    // fades the roof of whichever building the player is standing in
+    pub fn update_roofs(&mut self, player_position: Vector2f) {
+        let player_tile = world_to_tile(player_position);
+        let indoors = matches!(self.get_layer_tile(Layer::ROOF, player_tile.0, player_tile.1), Some(tile) if tile != EMPTY);
+
+        // only flood fill again once the player moves onto another tile
+        if indoors && self.player_tile != Some(player_tile) && !self.hidden_roof.contains(&player_tile) {
+            self.hidden_roof = self.connected_roof(player_tile);
+        }
+        self.player_tile = Some(player_tile);
+
+        if indoors {
+            self.roof_alpha = (self.roof_alpha - ROOF_FADE_STEP).max(0.0);
+        } else {
+            self.roof_alpha = (self.roof_alpha + ROOF_FADE_STEP).min(1.0);
+            if self.roof_alpha >= 1.0 {
+                self.hidden_roof.clear();
+            }
+        }
+    }
+
+    // every roof tile connected to start, buildings are kept apart so this is one roof
+    fn connected_roof(&self, start: (i32, i32)) -> HashSet<(i32, i32)> {
+        let mut roof = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+
+        while let Some((tile_x, tile_y)) = queue.pop_front() {
+            for next in [(tile_x + 1, tile_y), (tile_x - 1, tile_y), (tile_x, tile_y + 1), (tile_x, tile_y - 1)] {
+                if roof.len() >= MAX_ROOF_TILES || roof.contains(&next) {
+                    continue;
+                }
+
+                if matches!(self.get_layer_tile(Layer::ROOF, next.0, next.1), Some(tile) if tile != EMPTY) {
+                    roof.insert(next);
+                    queue.push_back(next);
+                }
+            }
+        }
+
+        roof
+    }
+
+    pub fn get_chunk(&self, chunk_position: (i32, i32)) -> Option<&Chunk> {
+        self.chunks.get(&chunk_position)
+    }
+
+    // tile ID at world tile coordinates, None if that chunk isn't loaded
+    pub fn get_tile(&self, tile_x: i32, tile_y: i32) -> Option<u16> {
+        let (chunk_position, (local_x, local_y)) = tile_to_chunk(tile_x, tile_y);
+
+        self.chunks
+            .get(&chunk_position)
+            .map(|chunk| chunk.get_tile(local_x, local_y))
+    }
+
+    // tile ID on any layer at world tile coordinates, EMPTY if there's nothing on that layer
+    pub fn get_layer_tile(&self, layer: Layer, tile_x: i32, tile_y: i32) -> Option<u16> {
+        let (chunk_position, (local_x, local_y)) = tile_to_chunk(tile_x, tile_y);
+
+        self.chunks
+            .get(&chunk_position)
+            .map(|chunk| chunk.get_layer_tile(layer, local_x, local_y))
+    }
+
+    // both the ground and whatever stands on it have to be walkable, and water can only be
+    // crossed by swimming. unloaded chunks aren't walkable, so nothing wanders off into parts
+    // of the world that don't exist yet
+    pub fn is_walkable(&self, tile_x: i32, tile_y: i32, can_swim: bool) -> bool {
+        let tiles = &self.assets.tiles;
+
+        match (self.get_tile(tile_x, tile_y), self.get_layer_tile(Layer::WALLS, tile_x, tile_y)) {
+            (Some(ground), Some(wall)) => {
+                let ground = tiles.get(ground);
+                ground.walkable && (can_swim || !ground.swim) && (wall == EMPTY || tiles.get(wall).walkable)
+            },
+            _ => false,
+        }
+    }
+
+    // what movement collides with: unwalkable tiles plus solid props
+    pub fn is_blocked(&self, tile_x: i32, tile_y: i32, can_swim: bool) -> bool {
+        if !self.is_walkable(tile_x, tile_y, can_swim) {
+            return true;
+        }
+
+        let (chunk_position, (local_x, local_y)) = tile_to_chunk(tile_x, tile_y);
+        self.chunks
+            .get(&chunk_position)
+            .is_some_and(|chunk| {
+                chunk.props
+                    .iter()
+                    .any(|prop| prop.kind.is_solid() && (prop.tile_x as usize, prop.tile_y as usize) == (local_x, local_y))
+            })
+    }
+
+    // how fast things move over the ground at a position, 1.0 where nothing is loaded
+    pub fn speed_multiplier(&self, world_position: Vector2f) -> f32 {
+        self.tile_at(world_position)
+            .map(|tile| self.assets.tiles.get(tile).speed_multiplier)
+            .unwrap_or(1.0)
+    }
+
+    pub fn is_water(&self, tile_x: i32, tile_y: i32) -> bool {
+        self.get_tile(tile_x, tile_y)
+            .is_some_and(|tile| self.assets.tiles.get(tile).swim)
+    }
+
+    // how well lit the ground at a position is, from 0 for pitch black up to 1
+    pub fn light_level(&self, position: Vector2f) -> f32 {
+        let (tile_x, tile_y) = world_to_tile(position);
+
+        match self.get_layer_tile(Layer::ROOF, tile_x, tile_y) {
+            Some(roof) if roof != EMPTY => INDOOR_LIGHT,
+            _ => 1.0,
+        }
+    }
+
+    pub fn blocks_sight(&self, tile_x: i32, tile_y: i32) -> bool {
+        match self.get_layer_tile(Layer::WALLS, tile_x, tile_y) {
+            Some(wall) => wall != EMPTY && self.assets.tiles.get(wall).blocks_sight,
+            None => false,
+        }
+    }
+
+    // true if nothing that blocks sight is on any tile the line between the two points passes
+    pub fn line_of_sight(&self, from: Vector2f, to: Vector2f) -> bool {
+        tiles_on_line(from, to)
+            .into_iter()
+            .all(|(tile_x, tile_y)| !self.blocks_sight(tile_x, tile_y))
+    }
+
+    // how many sight blocking tiles the line between the two points passes through
+    pub fn walls_between(&self, from: Vector2f, to: Vector2f) -> usize {
+        tiles_on_line(from, to)
+            .into_iter()
+            .filter(|(tile_x, tile_y)| self.blocks_sight(*tile_x, *tile_y))
+            .count()
+    }
+
+    // how far along the ray the first sight blocking tile starts, None if there's none within length
+    pub fn wall_distance(&self, from: Vector2f, direction: Vector2f, length: f32) -> Option<f32> {
+        tiles_on_line(from, from + direction * length)
+            .into_iter()
+            .find(|(tile_x, tile_y)| self.blocks_sight(*tile_x, *tile_y))
+            .and_then(|(tile_x, tile_y)| {
+                let size = TILE_SIZE as f32;
+                ray_box(from, direction, FloatRect::new(tile_x as f32 * size, tile_y as f32 * size, size, size))
+            })
+    }
+
+    // opens closed doors and closes open ones, false if there's no door at that tile
+    pub fn toggle_door(&mut self, tile_x: i32, tile_y: i32) -> bool {
+        let (chunk_position, (local_x, local_y)) = tile_to_chunk(tile_x, tile_y);
+
+        let Some(chunk) = self.chunks.get_mut(&chunk_position) else {
+            return false;
+        };
+
+        let toggled = match chunk.get_layer_tile(Layer::WALLS, local_x, local_y) {
+            DOOR => DOOR_OPEN,
+            DOOR_OPEN => DOOR,
+            _ => return false,
+        };
+
+        chunk.set_layer_tile(Layer::WALLS, local_x, local_y, toggled);
+        true
+    }
+
+    // tile ID under a world position in pixels
+    pub fn tile_at(&self, world_position: Vector2f) -> Option<u16> {
+        let (tile_x, tile_y) = world_to_tile(world_position);
+        self.get_tile(tile_x, tile_y)
+    }
+
+    // returns false if the chunk under the position isn't loaded, nothing is changed then
+    pub fn set_tile(&mut self, world_position: Vector2f, tile: u16) -> bool {
+        let (tile_x, tile_y) = world_to_tile(world_position);
+        let (chunk_position, (local_x, local_y)) = tile_to_chunk(tile_x, tile_y);
+
+        match self.chunks.get_mut(&chunk_position) {
+            Some(chunk) => {
+                chunk.set_tile(local_x, local_y, tile);
+                true
+            },
+            None => false,
+        }
+    }
+
+    // one draw call per visible chunk, each chunk keeps its own vertex array of tile quads
+    pub fn render(&self, window: &mut RenderWindow, camera_offset: Vector2f, display_size: Vector2f) {
+
+        let mut states = RenderStates::default();
+        states.set_texture(Some(&self.assets.terrain_texture));
+        states.transform.translate(-camera_offset.x, -camera_offset.y);
+
+        let visible: Vec<&Chunk> = visible_chunks(FloatRect::from_vecs(camera_offset, display_size))
+            .into_iter()
+            .filter_map(|chunk_position| self.chunks.get(&chunk_position))
+            .collect();
+
+        for chunk in visible.iter() {
+            window.draw_primitives(&chunk.vertices, PrimitiveType::QUADS, &states);
+        }
+
+        // props go on top of every chunk so they aren't cut off by the neighbouring chunk's tiles
+        let mut shape = CircleShape::new(1.0, 12);
+        for chunk in visible.iter() {
+            for prop in chunk.props.iter() {
+                let radius = prop.kind.radius();
+                let center = Vector2f::new(
+                    (chunk.x + prop.tile_x as i32 * TILE_SIZE + TILE_SIZE / 2) as f32,
+                    (chunk.y + prop.tile_y as i32 * TILE_SIZE + TILE_SIZE / 2) as f32,
+                );
+
+                shape.set_radius(radius);
+                shape.set_fill_color(prop.kind.color());
+                shape.set_position(center - Vector2f::new(radius, radius) - camera_offset);
+                window.draw(&shape);
+            }
+        }
+    }
+
+    // has to go after the entities so they're hidden under roofs. chunks without any of the
+    // faded roof draw their cached roof straight away, the rest get a copy with the alpha changed
+    pub fn render_roofs(&self, window: &mut RenderWindow, camera_offset: Vector2f, display_size: Vector2f) {
+        let mut states = RenderStates::default();
+        states.set_texture(Some(&self.assets.terrain_texture));
+        states.transform.translate(-camera_offset.x, -camera_offset.y);
+
+        let alpha = (self.roof_alpha * 255.0) as u8;
+
+        for chunk_position in visible_chunks(FloatRect::from_vecs(camera_offset, display_size)) {
+            let Some(chunk) = self.chunks.get(&chunk_position) else {
+                continue;
+            };
+
+            if chunk.roof_vertices.is_empty() {
+                continue;
+            }
+
+            if self.hidden_roof.is_empty() || alpha == 255 {
+                window.draw_primitives(&chunk.roof_vertices, PrimitiveType::QUADS, &states);
+                continue;
+            }
+
+            let mut vertices = chunk.roof_vertices.clone();
+            for quad in vertices.chunks_exact_mut(4) {
+                // the first corner of a quad is its tile's top left
+                if self.hidden_roof.contains(&world_to_tile(quad[0].position)) {
+                    for vertex in quad.iter_mut() {
+                        vertex.color.a = alpha;
                     }
                 }
             }
+
+            window.draw_primitives(&vertices, PrimitiveType::QUADS, &states);
+        }
+    }
+}
+
+impl Drop for Map {
+    // flush every modified chunk, loaded or cached, before the generator thread goes away
+    fn drop(&mut self) {
+        let modified_chunks: Vec<Chunk> = self.chunks
+            .drain()
+            .map(|(_, chunk)| chunk)
+            .chain(self.cache.drain())
+            .filter(|chunk| chunk.modified)
+            .collect();
+
+        if !modified_chunks.is_empty() {
+            let _ = self.chunk_generator_sender.send(ChunkRequest::Save(modified_chunks));
+        }
+
+        let _ = self.chunk_generator_sender.send(ChunkRequest::Shutdown);
+
+        if let Some(chunk_generator) = self.chunk_generator.take() {
+            let _ = chunk_generator.join();
         }
     }
 }
 
+// chunk coordinate containing a world position in pixels
+pub fn world_to_chunk(position: Vector2f) -> (i32, i32) {
+    (
+        (position.x / CHUNK_SIZE_PIXELS as f32).floor() as i32,
+        (position.y / CHUNK_SIZE_PIXELS as f32).floor() as i32,
+    )
+}
+
+// world tile coordinate containing a world position in pixels. floors instead of truncating
+// so -0.5 lands in tile -1, not tile 0
+pub fn world_to_tile(position: Vector2f) -> (i32, i32) {
+    (
+        (position.x / TILE_SIZE as f32).floor() as i32,
+        (position.y / TILE_SIZE as f32).floor() as i32,
+    )
+}
+
+// splits a world tile coordinate into the chunk it's in and its position inside that chunk
+pub fn tile_to_chunk(tile_x: i32, tile_y: i32) -> ((i32, i32), (usize, usize)) {
+    (
+        (tile_x.div_euclid(CHUNK_SIZE as i32), tile_y.div_euclid(CHUNK_SIZE as i32)),
+        (tile_x.rem_euclid(CHUNK_SIZE as i32) as usize, tile_y.rem_euclid(CHUNK_SIZE as i32) as usize),
+    )
+}
+
+// chunk coordinates of every chunk that overlaps the camera rect, including the ones only
+// partly on screen. the right and bottom edges are exclusive
+pub fn visible_chunks(camera: FloatRect) -> Vec<(i32, i32)> {
+    let chunk_size = CHUNK_SIZE_PIXELS as f32;
+
+    let first_x = (camera.left / chunk_size).floor() as i32;
+    let first_y = (camera.top / chunk_size).floor() as i32;
+    let last_x = ((camera.left + camera.width) / chunk_size).ceil() as i32 - 1;
+    let last_y = ((camera.top + camera.height) / chunk_size).ceil() as i32 - 1;
+
+    let mut positions = Vec::new();
+
+    for x in first_x..=last_x {
+        for y in first_y..=last_y {
+            positions.push((x, y));
+        }
+    }
+
+    positions
+}
+
+// every world tile a straight line passes through, in order, both ends included
+pub fn tiles_on_line(from: Vector2f, to: Vector2f) -> Vec<(i32, i32)> {
+    let (mut tile_x, mut tile_y) = world_to_tile(from);
+    let last = world_to_tile(to);
+
+    let delta = to - from;
+    let step_x = if delta.x > 0.0 { 1 } else { -1 };
+    let step_y = if delta.y > 0.0 { 1 } else { -1 };
+
+    // distance along the line, as a fraction of its length, to the next tile border on each axis
+    let size = TILE_SIZE as f32;
+    let next_border = |position: f32, tile: i32, step: i32| if step > 0 { (tile + 1) as f32 * size - position } else { position - tile as f32 * size };
+    let mut next_x = if delta.x != 0.0 { next_border(from.x, tile_x, step_x) / delta.x.abs() } else { f32::INFINITY };
+    let mut next_y = if delta.y != 0.0 { next_border(from.y, tile_y, step_y) / delta.y.abs() } else { f32::INFINITY };
+    let step_length_x = if delta.x != 0.0 { size / delta.x.abs() } else { f32::INFINITY };
+    let step_length_y = if delta.y != 0.0 { size / delta.y.abs() } else { f32::INFINITY };
+
+    // one step per tile border crossed, so rounding can never make this run past the end
+    let steps = (last.0 - tile_x).abs() + (last.1 - tile_y).abs();
+    let mut tiles = vec![(tile_x, tile_y)];
+
+    for _ in 0..steps {
+        if next_x < next_y {
+            tile_x += step_x;
+            next_x += step_length_x;
+        } else {
+            tile_y += step_y;
+            next_y += step_length_y;
+        }
+
+        tiles.push((tile_x, tile_y));
+    }
+
+    tiles
+}
+
+// every chunk coordinate within radius chunks of center, nearest first
+pub fn chunks_in_radius(center: (i32, i32), radius: i32) -> Vec<(i32, i32)> {
+    let mut positions = Vec::new();
+
+    for x in -radius..=radius {
+        for y in -radius..=radius {
+            if x * x + y * y <= radius * radius {
+                positions.push((center.0 + x, center.1 + y));
+            }
+        }
+    }
+
+    positions.sort_by_key(|(x, y)| (x - center.0).pow(2) + (y - center.1).pow(2));
+    positions
+}
+
 pub struct Chunk {
     x: i32,
     y: i32,
     tiles: [[u16; CHUNK_SIZE]; CHUNK_SIZE],
+    walls: [[u16; CHUNK_SIZE]; CHUNK_SIZE],
+    roof: [[u16; CHUNK_SIZE]; CHUNK_SIZE],
+    pub biome: Biome,
+    pub props: Vec<Prop>,
+    // set once anything changes the tiles, modified chunks get saved instead of regenerated
+    modified: bool,
+    // textured quads for every tile in world coordinates, rebuilt whenever the tiles change.
+    // walls come after the ground so they're drawn over it, roofs are drawn on their own
+    vertices: Vec<Vertex>,
+    roof_vertices: Vec<Vertex>,
+    vertices_dirty: bool,
 }
 
 impl Chunk {
+    fn new(x: i32, y: i32, tiles: [[u16; CHUNK_SIZE]; CHUNK_SIZE], modified: bool) -> Self {
+        Chunk {
+            x,
+            y,
+            tiles,
+            walls: [[EMPTY; CHUNK_SIZE]; CHUNK_SIZE],
+            roof: [[EMPTY; CHUNK_SIZE]; CHUNK_SIZE],
+            biome: Biome::PLAINS,
+            props: Vec::new(),
+            modified,
+            vertices: Vec::new(),
+            roof_vertices: Vec::new(),
+            vertices_dirty: true,
+        }
+    }
+
+    pub fn build_vertices(&mut self, registry: &TileRegistry) {
+        let mut vertices = std::mem::take(&mut self.vertices);
+        let mut roof_vertices = std::mem::take(&mut self.roof_vertices);
+        vertices.clear();
+        roof_vertices.clear();
+
+        self.push_layer_vertices(&self.tiles, registry, &mut vertices);
+        self.push_layer_vertices(&self.walls, registry, &mut vertices);
+        self.push_layer_vertices(&self.roof, registry, &mut roof_vertices);
+
+        self.vertices = vertices;
+        self.roof_vertices = roof_vertices;
+        self.vertices_dirty = false;
+    }
+
+    fn push_layer_vertices(&self, layer: &[[u16; CHUNK_SIZE]; CHUNK_SIZE], registry: &TileRegistry, vertices: &mut Vec<Vertex>) {
+        for (tile_x, tile_column) in layer.iter().enumerate() {
+            for (tile_y, tile) in tile_column.iter().enumerate() {
+                if *tile == EMPTY {
+                    continue;
+                }
+
+                let left = (self.x + tile_x as i32 * TILE_SIZE) as f32;
+                let top = (self.y + tile_y as i32 * TILE_SIZE) as f32;
+                let size = TILE_SIZE as f32;
+
+                let definition = registry.get(*tile);
+                let color = definition.color();
+
+                let texture_rect = definition.texture_rect();
+                let texture_left = texture_rect.left as f32;
+                let texture_top = texture_rect.top as f32;
+                let texture_right = (texture_rect.left + texture_rect.width) as f32;
+                let texture_bottom = (texture_rect.top + texture_rect.height) as f32;
+
+                vertices.extend_from_slice(&[
+                    Vertex::new(Vector2f::new(left, top), color, Vector2f::new(texture_left, texture_top)),
+                    Vertex::new(Vector2f::new(left + size, top), color, Vector2f::new(texture_right, texture_top)),
+                    Vertex::new(Vector2f::new(left + size, top + size), color, Vector2f::new(texture_right, texture_bottom)),
+                    Vertex::new(Vector2f::new(left, top + size), color, Vector2f::new(texture_left, texture_bottom)),
+                ]);
+            }
+        }
+    }
+
     pub fn template(x: i32, y: i32) -> Self {
         let tiles: [[u16; CHUNK_SIZE]; CHUNK_SIZE] = [
             [1, 1, 1, 1, 1, 1, 1, 1],
@@ -131,37 +690,629 @@ impl Chunk {
             [1, 1, 1, 1, 1, 1, 1, 1],
         ];
 
-        Chunk {
-            x, 
-            y, 
-            tiles
-        }
-    }
-
-    pub fn random(x: i32, y: i32, seed: u32) -> Self {
-        let mut rng = StdRng::seed_from_u64((seed as u64 * x.abs() as u64 * y.abs() as u64) as u64);
-        println!("Seeding with: {}", (seed as u64 * x.abs() as u64 * y.abs() as u64) as u64);
-        let mut tiles: [[u16; CHUNK_SIZE]; CHUNK_SIZE] = [
-            [0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0],
-            [0, 0, 0, 0, 0, 0, 0, 0],
+        Chunk::new(x, y, tiles, false)
+    }
+
+    pub fn chunk_position(&self) -> (i32, i32) {
+        (
+            self.x.div_euclid(CHUNK_SIZE_PIXELS as i32),
+            self.y.div_euclid(CHUNK_SIZE_PIXELS as i32),
+        )
+    }
+
+    pub fn memory_size(&self) -> usize {
+        std::mem::size_of::<Chunk>()
+            + (self.vertices.capacity() + self.roof_vertices.capacity()) * std::mem::size_of::<Vertex>()
+            + self.props.capacity() * std::mem::size_of::<Prop>()
+    }
+
+    pub fn get_tile(&self, tile_x: usize, tile_y: usize) -> u16 {
+        self.tiles[tile_x][tile_y]
+    }
+
+    pub fn set_tile(&mut self, tile_x: usize, tile_y: usize, tile: u16) {
+        self.set_layer_tile(Layer::GROUND, tile_x, tile_y, tile);
+    }
+
+    fn layer(&self, layer: Layer) -> &[[u16; CHUNK_SIZE]; CHUNK_SIZE] {
+        match layer {
+            Layer::GROUND => &self.tiles,
+            Layer::WALLS => &self.walls,
+            Layer::ROOF => &self.roof,
+        }
+    }
+
+    fn layer_mut(&mut self, layer: Layer) -> &mut [[u16; CHUNK_SIZE]; CHUNK_SIZE] {
+        match layer {
+            Layer::GROUND => &mut self.tiles,
+            Layer::WALLS => &mut self.walls,
+            Layer::ROOF => &mut self.roof,
+        }
+    }
+
+    pub fn get_layer_tile(&self, layer: Layer, tile_x: usize, tile_y: usize) -> u16 {
+        self.layer(layer)[tile_x][tile_y]
+    }
+
+    pub fn set_layer_tile(&mut self, layer: Layer, tile_x: usize, tile_y: usize, tile: u16) {
+        let current = &mut self.layer_mut(layer)[tile_x][tile_y];
+
+        if *current != tile {
+            *current = tile;
+            self.modified = true;
+            self.vertices_dirty = true;
+        }
+    }
+
+    fn file_path(save_directory: &Path, position: (i32, i32)) -> PathBuf {
+        save_directory.join(format!("{}_{}.chunk", position.0, position.1))
+    }
+
+    // file layout: version byte, biome byte, the ground, wall and roof layers with every tile as
+    // a little endian u16 column by column, then a prop count byte followed by kind, x, y bytes
+    // for each prop
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![CHUNK_FILE_VERSION, self.biome as u8];
+
+        for layer in [&self.tiles, &self.walls, &self.roof] {
+            for tile_column in layer.iter() {
+                for tile in tile_column {
+                    bytes.extend_from_slice(&tile.to_le_bytes());
+                }
+            }
+        }
+
+        bytes.push(self.props.len() as u8);
+        for prop in self.props.iter() {
+            bytes.extend_from_slice(&[prop.kind as u8, prop.tile_x, prop.tile_y]);
+        }
+
+        bytes
+    }
+
+    pub fn from_bytes(position: (i32, i32), bytes: &[u8]) -> Option<Self> {
+        const LAYER_BYTES: usize = CHUNK_SIZE * CHUNK_SIZE * 2;
+
+        let (version, bytes) = bytes.split_first()?;
+        let (biome, bytes) = bytes.split_first()?;
+        if *version != CHUNK_FILE_VERSION || bytes.len() < LAYER_BYTES * 3 + 1 {
+            return None;
+        }
+
+        let (layer_bytes, bytes) = bytes.split_at(LAYER_BYTES * 3);
+        let (prop_count, prop_bytes) = bytes.split_first()?;
+        if prop_bytes.len() != *prop_count as usize * 3 {
+            return None;
+        }
+
+        let mut layers = [[[EMPTY; CHUNK_SIZE]; CHUNK_SIZE]; 3];
+        for (layer, layer_bytes) in layers.iter_mut().zip(layer_bytes.chunks_exact(LAYER_BYTES)) {
+            for (index, tile_bytes) in layer_bytes.chunks_exact(2).enumerate() {
+                layer[index / CHUNK_SIZE][index % CHUNK_SIZE] = u16::from_le_bytes([tile_bytes[0], tile_bytes[1]]);
+            }
+        }
+        let [tiles, walls, roof] = layers;
+
+        let mut props = Vec::new();
+        for prop_bytes in prop_bytes.chunks_exact(3) {
+            props.push(Prop {
+                kind: PropKind::from_id(prop_bytes[0])?,
+                tile_x: prop_bytes[1],
+                tile_y: prop_bytes[2],
+            });
+        }
+
+        // it differs from what the generator would make, so it has to keep being saved
+        let mut chunk = Chunk::new(
+            position.0 * CHUNK_SIZE_PIXELS as i32,
+            position.1 * CHUNK_SIZE_PIXELS as i32,
+            tiles,
+            true,
+        );
+        chunk.walls = walls;
+        chunk.roof = roof;
+        chunk.biome = Biome::from_id(*biome)?;
+        chunk.props = props;
+
+        Some(chunk)
+    }
+
+    pub fn save(&self, save_directory: &Path) -> io::Result<()> {
+        fs::create_dir_all(save_directory)?;
+        fs::write(Chunk::file_path(save_directory, self.chunk_position()), self.to_bytes())
+    }
+
+    // None if the chunk was never saved or the file can't be read
+    pub fn load(save_directory: &Path, position: (i32, i32)) -> Option<Self> {
+        let path = Chunk::file_path(save_directory, position);
+        if !path.exists() {
+            return None;
+        }
+
+        let chunk = fs::read(&path)
+            .ok()
+            .and_then(|bytes| Chunk::from_bytes(position, &bytes));
+
+        if chunk.is_none() {
+            println!("Failed to load chunk {:?}, regenerating it", position);
+        }
+
+        chunk
+    }
+
+    // x and y are the chunk's position in pixels, tiles are filled from the world generator
+    // so neighbouring chunks line up at their borders
+    pub fn generate(x: i32, y: i32, generator: &WorldGenerator) -> Self {
+        let mut tiles: [[u16; CHUNK_SIZE]; CHUNK_SIZE] = [[GRASS; CHUNK_SIZE]; CHUNK_SIZE];
+        let mut walls = [[EMPTY; CHUNK_SIZE]; CHUNK_SIZE];
+        let mut roof = [[EMPTY; CHUNK_SIZE]; CHUNK_SIZE];
+        let mut props = Vec::new();
+
+        let tile_origin_x = x / TILE_SIZE;
+        let tile_origin_y = y / TILE_SIZE;
+
+        let chunk_x = x.div_euclid(CHUNK_SIZE_PIXELS as i32);
+        let chunk_y = y.div_euclid(CHUNK_SIZE_PIXELS as i32);
+
+        let mut rng = StdRng::seed_from_u64(generator.chunk_seed(chunk_x, chunk_y));
+        let structures = generator.structures_near_chunk(chunk_x, chunk_y);
+
+        for (tile_x, tile_column) in tiles.iter_mut().enumerate() {
+            for (tile_y, tile) in tile_column.iter_mut().enumerate() {
+                let world_tile_x = tile_origin_x + tile_x as i32;
+                let world_tile_y = tile_origin_y + tile_y as i32;
+
+                // roads and buildings are stamped over the terrain and keep it clear of props
+                if let Some(structure_tile) = structures.tile_at(world_tile_x, world_tile_y) {
+                    *tile = structure_tile;
+                    walls[tile_x][tile_y] = structures.wall_at(world_tile_x, world_tile_y).unwrap_or(EMPTY);
+                    roof[tile_x][tile_y] = structures.roof_at(world_tile_x, world_tile_y).unwrap_or(EMPTY);
+                    continue;
+                }
+
+                // water and its banks get no props either
+                if let Some(water_tile) = generator.water_at(world_tile_x, world_tile_y) {
+                    *tile = water_tile;
+                    continue;
+                }
+
+                // tiles and props follow the blended biome of their own tile, not the chunk's
+                let biome = generator.biome_at(world_tile_x, world_tile_y);
+                *tile = generator.tile_at_in(biome, world_tile_x, world_tile_y);
+
+                if rng.gen::<f32>() < biome.prop_density() {
+                    props.push(Prop {
+                        kind: pick_weighted(biome.props(), rng.gen()),
+                        tile_x: tile_x as u8,
+                        tile_y: tile_y as u8,
+                    });
+                }
+            }
+        }
+
+        let mut chunk = Chunk::new(x, y, tiles, false);
+        chunk.walls = walls;
+        chunk.roof = roof;
+        chunk.biome = generator.chunk_biome(chunk_x, chunk_y);
+        chunk.props = props;
+
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, time::Instant};
+
+    use sfml::graphics::{Color, RenderTexture, Sprite, Texture};
+
+    use crate::{entities::zombie::ZombieKind, generation::LAKE_BELOW, spawn_director::SpawnDirector};
+    use super::*;
+
+    const TEST_SEED: u32 = 123456789;
+
+    fn chunk_at(chunk_x: i32, chunk_y: i32, generator: &WorldGenerator) -> Chunk {
+        Chunk::generate(chunk_x * CHUNK_SIZE_PIXELS as i32, chunk_y * CHUNK_SIZE_PIXELS as i32, generator)
+    }
+
+    #[test]
+    fn chunk_seeds_are_unique() {
+        let generator = WorldGenerator::new(TEST_SEED);
+
+        let mut seeds = HashSet::new();
+        for chunk_x in -8..=8 {
+            for chunk_y in -8..=8 {
+                assert!(seeds.insert(generator.chunk_seed(chunk_x, chunk_y)), "duplicate seed at ({}, {})", chunk_x, chunk_y);
+            }
+        }
+    }
+
+    #[test]
+    fn chunks_differ_across_quadrants() {
+        let generator = WorldGenerator::new(TEST_SEED);
+
+        // axes, the origin, and a chunk mirrored into every quadrant plus its transpose
+        let coordinates = [
+            (0, 0), (1, 0), (0, 1), (-1, 0), (0, -1),
+            (2, 3), (-2, 3), (2, -3), (-2, -3),
+            (3, 2), (-3, 2), (3, -2), (-3, -2),
         ];
 
-        for tile_row in tiles.iter_mut() {
-            for tile in tile_row {
-                *tile = rng.gen_range(0..=3);
+        let chunks: Vec<Chunk> = coordinates
+            .iter()
+            .map(|(chunk_x, chunk_y)| chunk_at(*chunk_x, *chunk_y, &generator))
+            .collect();
+
+        for first in 0..chunks.len() {
+            for second in (first + 1)..chunks.len() {
+                assert_ne!(
+                    chunks[first].tiles, chunks[second].tiles,
+                    "chunks {:?} and {:?} are identical", coordinates[first], coordinates[second]
+                );
             }
         }
+    }
 
-        Chunk {
-            x,
-            y,
-            tiles
+    #[test]
+    fn world_positions_floor_into_tiles() {
+        assert_eq!(world_to_tile(Vector2f::new(0.0, 31.9)), (0, 0));
+        assert_eq!(world_to_tile(Vector2f::new(-0.5, -32.0)), (-1, -1));
+        assert_eq!(world_to_tile(Vector2f::new(-32.5, 64.0)), (-2, 2));
+
+        assert_eq!(world_to_chunk(Vector2f::new(-0.5, 255.9)), (-1, 0));
+        assert_eq!(world_to_chunk(Vector2f::new(-256.0, -256.5)), (-1, -2));
+    }
+
+    #[test]
+    fn tiles_split_into_chunk_and_local_position() {
+        assert_eq!(tile_to_chunk(0, 0), ((0, 0), (0, 0)));
+        assert_eq!(tile_to_chunk(7, 8), ((0, 1), (7, 0)));
+        assert_eq!(tile_to_chunk(-1, -1), ((-1, -1), (7, 7)));
+        assert_eq!(tile_to_chunk(-8, -9), ((-1, -2), (0, 7)));
+    }
+
+    #[test]
+    fn lines_pass_through_every_tile_they_touch() {
+        assert_eq!(tiles_on_line(Vector2f::new(16.0, 16.0), Vector2f::new(16.0, 16.0)), vec![(0, 0)]);
+        assert_eq!(
+            tiles_on_line(Vector2f::new(16.0, 16.0), Vector2f::new(-80.0, 16.0)),
+            vec![(0, 0), (-1, 0), (-2, 0), (-3, 0)]
+        );
+
+        // a diagonal crosses one border at a time, never skipping a corner tile
+        let tiles = tiles_on_line(Vector2f::new(10.0, 20.0), Vector2f::new(100.0, 70.0));
+        assert_eq!(tiles.first(), Some(&(0, 0)));
+        assert_eq!(tiles.last(), Some(&(3, 2)));
+        for pair in tiles.windows(2) {
+            assert_eq!((pair[1].0 - pair[0].0).abs() + (pair[1].1 - pair[0].1).abs(), 1);
+        }
+    }
+
+    #[test]
+    fn towns_generate_walls_doors_and_roofs() {
+        let generator = WorldGenerator::new(TEST_SEED);
+        let town = (0..5)
+            .flat_map(|cell_x| (0..5).map(move |cell_y| (cell_x, cell_y)))
+            .find_map(|(cell_x, cell_y)| generator.town(cell_x, cell_y))
+            .unwrap();
+        let building = town.buildings[0];
+
+        let (chunk_position, (local_x, local_y)) = tile_to_chunk(building.door.0, building.door.1);
+        let chunk = chunk_at(chunk_position.0, chunk_position.1, &generator);
+        assert_eq!(chunk.get_layer_tile(Layer::WALLS, local_x, local_y), DOOR);
+        assert_eq!(chunk.get_layer_tile(Layer::ROOF, local_x, local_y), ROOF);
+        assert_eq!(chunk.get_tile(local_x, local_y), FLOOR);
+
+        let (chunk_position, (local_x, local_y)) = tile_to_chunk(building.left + 1, building.top + 1);
+        let chunk = chunk_at(chunk_position.0, chunk_position.1, &generator);
+        assert_eq!(chunk.get_layer_tile(Layer::WALLS, local_x, local_y), EMPTY);
+        assert_eq!(chunk.get_layer_tile(Layer::ROOF, local_x, local_y), ROOF);
+    }
+
+    #[test]
+    fn rivers_cross_chunk_borders() {
+        let generator = WorldGenerator::new(TEST_SEED);
+
+        // water comes from world tile coordinates only, so chunks on either side of a border
+        // agree on it. roads are the only thing allowed to cross it, as bridges
+        let is_river = |tile_x, tile_y| {
+            generator.water_at(tile_x, tile_y) == Some(WATER) && generator.elevation(tile_x, tile_y) >= LAKE_BELOW
+        };
+
+        let mut crossings = 0;
+        for chunk_x in -6..6 {
+            for chunk_y in -6..6 {
+                let chunk = chunk_at(chunk_x, chunk_y, &generator);
+                let right = chunk_at(chunk_x + 1, chunk_y, &generator);
+
+                for tile_y in 0..CHUNK_SIZE {
+                    let world_tile_y = chunk_y * CHUNK_SIZE as i32 + tile_y as i32;
+                    let edge_x = chunk_x * CHUNK_SIZE as i32 + CHUNK_SIZE as i32 - 1;
+
+                    let left_water = generator.water_at(edge_x, world_tile_y) == Some(WATER);
+                    let right_water = generator.water_at(edge_x + 1, world_tile_y) == Some(WATER);
+
+                    if left_water && chunk.get_tile(CHUNK_SIZE - 1, tile_y) != ASPHALT {
+                        assert_eq!(chunk.get_tile(CHUNK_SIZE - 1, tile_y), WATER);
+                    }
+                    if right_water && right.get_tile(0, tile_y) != ASPHALT {
+                        assert_eq!(right.get_tile(0, tile_y), WATER);
+                    }
+                    // lakes sitting on a border don't count, only rivers running across it
+                    if is_river(edge_x, world_tile_y) && is_river(edge_x + 1, world_tile_y) {
+                        crossings += 1;
+                    }
+                }
+            }
+        }
+
+        assert!(crossings > 0);
+    }
+
+    fn sorted_visible_chunks(left: f32, top: f32, width: f32, height: f32) -> Vec<(i32, i32)> {
+        let mut positions = visible_chunks(FloatRect::new(left, top, width, height));
+        positions.sort();
+        positions
+    }
+
+    #[test]
+    fn culling_covers_the_screen() {
+        let positions = sorted_visible_chunks(0.0, 0.0, 1200.0, 800.0);
+
+        // 1200 / 256 = 4.7 chunks across, 800 / 256 = 3.1 chunks down
+        assert_eq!(positions.len(), 5 * 4);
+        assert_eq!(positions.first(), Some(&(0, 0)));
+        assert_eq!(positions.last(), Some(&(4, 3)));
+    }
+
+    #[test]
+    fn culling_handles_negative_coordinates() {
+        assert_eq!(
+            sorted_visible_chunks(-300.0, -10.0, 100.0, 100.0),
+            vec![(-2, -1), (-2, 0), (-1, -1), (-1, 0)]
+        );
+
+        // far from the origin chunks must not be treated as huge
+        assert_eq!(sorted_visible_chunks(-10000.0, -10000.0, 10.0, 10.0), vec![(-40, -40)]);
+        assert_eq!(sorted_visible_chunks(10000.0, 10000.0, 10.0, 10.0), vec![(39, 39)]);
+    }
+
+    #[test]
+    fn culling_includes_partly_visible_chunks() {
+        assert_eq!(sorted_visible_chunks(100.0, 100.0, 10.0, 10.0), vec![(0, 0)]);
+        assert_eq!(sorted_visible_chunks(250.0, 0.0, 10.0, 10.0), vec![(0, 0), (1, 0)]);
+
+        // touching a chunk's edge isn't overlapping it
+        assert_eq!(sorted_visible_chunks(0.0, 0.0, 256.0, 256.0), vec![(0, 0)]);
+    }
+
+    #[test]
+    fn chunk_bytes_round_trip() {
+        let generator = WorldGenerator::new(TEST_SEED);
+
+        let mut chunk = chunk_at(-2, 5, &generator);
+        chunk.set_tile(3, 4, STONE);
+        assert!(chunk.modified);
+
+        chunk.set_layer_tile(Layer::WALLS, 0, 6, DOOR_OPEN);
+        chunk.set_layer_tile(Layer::ROOF, 7, 0, ROOF);
+        chunk.props.push(Prop { kind: PropKind::ROCK, tile_x: 1, tile_y: 7 });
+
+        let loaded = Chunk::from_bytes((-2, 5), &chunk.to_bytes()).unwrap();
+        assert_eq!(loaded.tiles, chunk.tiles);
+        assert_eq!(loaded.walls, chunk.walls);
+        assert_eq!(loaded.roof, chunk.roof);
+        assert_eq!(loaded.biome, chunk.biome);
+        assert_eq!(loaded.props, chunk.props);
+        assert_eq!((loaded.x, loaded.y), (chunk.x, chunk.y));
+
+        assert!(Chunk::from_bytes((-2, 5), &[CHUNK_FILE_VERSION, 0, 0]).is_none());
+    }
+
+    fn test_registry() -> TileRegistry {
+        TileRegistry::from_toml(include_str!("../res/tiles.toml")).unwrap()
+    }
+
+    #[test]
+    fn vertices_follow_tile_changes() {
+        let registry = test_registry();
+
+        let mut chunk = Chunk::template(0, 0);
+        chunk.build_vertices(&registry);
+        assert_eq!(chunk.vertices.len(), CHUNK_SIZE * CHUNK_SIZE * 4);
+        assert!(chunk.roof_vertices.is_empty());
+
+        // walls add their own quads over the ground, roofs go in their own array
+        chunk.set_layer_tile(Layer::WALLS, 1, 1, WALL);
+        chunk.set_layer_tile(Layer::ROOF, 1, 1, ROOF);
+        chunk.build_vertices(&registry);
+        assert_eq!(chunk.vertices.len(), (CHUNK_SIZE * CHUNK_SIZE + 1) * 4);
+        assert_eq!(chunk.roof_vertices.len(), 4);
+
+        chunk.set_tile(2, 5, STONE);
+        assert!(chunk.vertices_dirty);
+        chunk.build_vertices(&registry);
+
+        let stone_rect = registry.get(STONE).texture_rect();
+        let first_vertex = chunk.vertices[(2 * CHUNK_SIZE + 5) * 4];
+        assert_eq!(first_vertex.position, Vector2f::new(2.0 * TILE_SIZE as f32, 5.0 * TILE_SIZE as f32));
+        assert_eq!(first_vertex.tex_coords, Vector2f::new(stone_rect.left as f32, stone_rect.top as f32));
+    }
+
+    // times the old sprite-per-tile renderer against one vertex array per chunk, drawn
+    // offscreen so it doesn't need a window. still needs a graphics driver, hence ignored
+    // cargo test draw_call_benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn draw_call_benchmark() {
+        const FRAMES: u32 = 100;
+
+        let generator = WorldGenerator::new(TEST_SEED);
+        let registry = test_registry();
+        let display_size = Vector2f::new(1200.0, 800.0);
+
+        let texture = Texture::from_file("res/textures/terrain_32.png").unwrap();
+        let mut target = RenderTexture::new(display_size.x as u32, display_size.y as u32).expect("No offscreen render target");
+
+        println!("{:>24} {:>8} {:>12} {:>12} {:>12} {:>12}", "camera", "chunks", "tile draws", "tile ms", "chunk draws", "chunk ms");
+
+        for camera_offset in [
+            Vector2f::new(0.0, 0.0),
+            Vector2f::new(-600.0, -400.0),
+            Vector2f::new(1000.5, -3000.25),
+        ] {
+            let visible: Vec<Chunk> = visible_chunks(FloatRect::from_vecs(camera_offset, display_size))
+                .into_iter()
+                .map(|(chunk_x, chunk_y)| {
+                    let mut chunk = chunk_at(chunk_x, chunk_y, &generator);
+                    chunk.build_vertices(&registry);
+                    chunk
+                })
+                .collect();
+
+            // the old renderer, one sprite drawn for every ground tile
+            let mut sprite = Sprite::with_texture(&texture);
+            let mut tile_draws = 0;
+            let start = Instant::now();
+            for _ in 0..FRAMES {
+                target.clear(Color::BLACK);
+                for chunk in visible.iter() {
+                    for (tile_x, tile_column) in chunk.tiles.iter().enumerate() {
+                        for (tile_y, tile) in tile_column.iter().enumerate() {
+                            sprite.set_texture_rect(registry.get(*tile).texture_rect());
+                            sprite.set_position(Vector2f::new(
+                                (chunk.x + tile_x as i32 * TILE_SIZE) as f32,
+                                (chunk.y + tile_y as i32 * TILE_SIZE) as f32,
+                            ) - camera_offset);
+                            target.draw(&sprite);
+                            tile_draws += 1;
+                        }
+                    }
+                }
+                target.display();
+            }
+            let per_tile = start.elapsed();
+
+            let mut states = RenderStates::default();
+            states.set_texture(Some(&texture));
+            states.transform.translate(-camera_offset.x, -camera_offset.y);
+
+            let mut chunk_draws = 0;
+            let start = Instant::now();
+            for _ in 0..FRAMES {
+                target.clear(Color::BLACK);
+                for chunk in visible.iter() {
+                    target.draw_primitives(&chunk.vertices, PrimitiveType::QUADS, &states);
+                    chunk_draws += 1;
+                }
+                target.display();
+            }
+            let batched = start.elapsed();
+
+            println!(
+                "{:>24} {:>8} {:>12} {:>12.3} {:>12} {:>12.3}",
+                format!("{:?}", (camera_offset.x, camera_offset.y)),
+                visible.len(),
+                tile_draws / FRAMES,
+                per_tile.as_secs_f64() * 1000.0 / FRAMES as f64,
+                chunk_draws / FRAMES,
+                batched.as_secs_f64() * 1000.0 / FRAMES as f64,
+            );
+            assert!(chunk_draws < tile_draws);
+        }
+    }
+
+    // chunks with the same biome all around them, so none of their tiles are blended
+    fn chunks_inside(biome: Biome, generator: &WorldGenerator, count: usize) -> Vec<Chunk> {
+        let mut chunks = Vec::new();
+
+        for chunk_x in -60..60 {
+            for chunk_y in -60..60 {
+                let surrounded = (-1..=1).all(|x| (-1..=1).all(|y| generator.chunk_biome(chunk_x + x, chunk_y + y) == biome));
+                if surrounded {
+                    chunks.push(chunk_at(chunk_x, chunk_y, generator));
+                }
+                if chunks.len() == count {
+                    return chunks;
+                }
+            }
+        }
+
+        chunks
+    }
+
+    #[test]
+    fn biomes_show_up_in_generated_chunks() {
+        let generator = WorldGenerator::new(TEST_SEED);
+
+        for biome in [Biome::FOREST, Biome::BEACH, Biome::PLAINS, Biome::QUARRY, Biome::TOWN_OUTSKIRTS] {
+            let chunks = chunks_inside(biome, &generator, 6);
+            assert_eq!(chunks.len(), 6, "not enough {} chunks", biome.name());
+
+            let palette: Vec<u16> = biome.palette().iter().map(|(tile, _)| *tile).collect();
+            let prop_kinds: Vec<PropKind> = biome.props().iter().map(|(kind, _)| *kind).collect();
+            let (mut open_tiles, mut props) = (0, 0);
+
+            for chunk in chunks.iter() {
+                assert_eq!(chunk.biome, biome);
+
+                let (chunk_x, chunk_y) = (chunk.x / CHUNK_SIZE_PIXELS as i32, chunk.y / CHUNK_SIZE_PIXELS as i32);
+                let structures = generator.structures_near_chunk(chunk_x, chunk_y);
+
+                for tile_x in 0..CHUNK_SIZE {
+                    for tile_y in 0..CHUNK_SIZE {
+                        let world_tile_x = chunk_x * CHUNK_SIZE as i32 + tile_x as i32;
+                        let world_tile_y = chunk_y * CHUNK_SIZE as i32 + tile_y as i32;
+
+                        // roads, buildings and water are stamped over the palette
+                        if structures.tile_at(world_tile_x, world_tile_y).is_some() || generator.water_at(world_tile_x, world_tile_y).is_some() {
+                            continue;
+                        }
+
+                        assert!(palette.contains(&chunk.tiles[tile_x][tile_y]), "{} has tile {}", biome.name(), chunk.tiles[tile_x][tile_y]);
+                        open_tiles += 1;
+                    }
+                }
+
+                assert!(chunk.props.iter().all(|prop| prop_kinds.contains(&prop.kind)));
+                props += chunk.props.len();
+            }
+
+            let density = props as f32 / open_tiles.max(1) as f32;
+            assert!((density - biome.prop_density()).abs() < 0.06, "{} has prop density {}", biome.name(), density);
+
+            // the director fills them up from the biome's own spawn table
+            let loaded: Vec<((i32, i32), Biome)> = chunks
+                .iter()
+                .map(|chunk| ((chunk.x / CHUNK_SIZE_PIXELS as i32, chunk.y / CHUNK_SIZE_PIXELS as i32), chunk.biome))
+                .collect();
+            let zombie_kinds: Vec<ZombieKind> = biome.spawn_table().kinds.iter().map(|(kind, _)| *kind).collect();
+
+            let mut director = SpawnDirector::new(generator.clone(), 1000);
+            let mut zombies = Vec::new();
+            for _ in 0..6000 {
+                for spawn in director.update(&loaded, &zombies, |_, _| false, |_| false) {
+                    assert!(zombie_kinds.contains(&spawn.kind));
+                    zombies.push(spawn.position);
+                }
+            }
+
+            let target: usize = loaded.iter().map(|(chunk, biome)| director.chunk_target(*chunk, *biome)).sum();
+            assert_eq!(zombies.len(), target);
+        }
+    }
+
+    #[test]
+    fn regenerated_chunks_match() {
+        let generator = WorldGenerator::new(TEST_SEED);
+
+        for chunk_x in -3..=3 {
+            for chunk_y in -3..=3 {
+                let first = chunk_at(chunk_x, chunk_y, &generator);
+                let second = chunk_at(chunk_x, chunk_y, &WorldGenerator::new(TEST_SEED));
+
+                assert_eq!(first.tiles, second.tiles);
+            }
         }
     }
 }
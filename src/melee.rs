@@ -0,0 +1,211 @@
+use sfml::system::Vector2f;
+
+use crate::entities::{entity::EntityType, health::{Damage, DamageKind}};
+
+// one kind of swing, tuned per zombie kind or for the player. times are in ticks
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SwingStats {
+    // from starting the swing to it landing, long enough to step out of the way
+    pub windup_ticks: u32,
+    // after it lands, before the next one can start
+    pub recovery_ticks: u32,
+    // pixels from the swinger's position, measured to the edge of whatever it hits
+    pub reach: f32,
+    // full width of the arc in degrees
+    pub arc: f32,
+    pub damage: f32,
+    // pixels whatever it hits gets shoved away
+    pub knockback: f32,
+    pub stamina_cost: f32,
+}
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SwingPhase {
+    READY = 0,
+    WINDING_UP = 1,
+    RECOVERING = 2,
+}
+
+// a swing landing this tick
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Swing {
+    pub origin: Vector2f,
+    // unit vector, the middle of the arc
+    pub facing: Vector2f,
+    pub stats: SwingStats,
+    // what swung it, it can't hit its own kind
+    pub swinger: EntityType,
+}
+
+// something a swing caught, push is how far to shove it
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct MeleeHit {
+    pub target: usize,
+    pub damage: Damage,
+    pub push: Vector2f,
+}
+
+// winds up, lands, recovers, then it's ready again
+#[derive(Clone, PartialEq, Debug)]
+pub struct Melee {
+    pub stats: SwingStats,
+    pub phase: SwingPhase,
+    // ticks left in the current phase
+    pub timer: u32,
+    // where the swing in progress is aimed, locked in when it starts
+    pub facing: Vector2f,
+}
+
+impl Melee {
+    pub fn new(stats: SwingStats) -> Self {
+        Melee {
+            stats,
+            phase: SwingPhase::READY,
+            timer: 0,
+            facing: Vector2f::new(0.0, 1.0),
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.phase == SwingPhase::READY
+    }
+
+    // false if it's still busy with the last one or there's no direction to swing in
+    pub fn start(&mut self, aim: Vector2f) -> bool {
+        if !self.is_ready() || aim.length_sq() == 0.0 {
+            return false;
+        }
+
+        self.phase = SwingPhase::WINDING_UP;
+        self.timer = self.stats.windup_ticks;
+        self.facing = aim / aim.length_sq().sqrt();
+        true
+    }
+
+    // once per tick, gives back the swing on the tick it lands
+    pub fn update(&mut self, origin: Vector2f, swinger: EntityType) -> Option<Swing> {
+        self.timer = self.timer.saturating_sub(1);
+        if self.timer > 0 {
+            return None;
+        }
+
+        match self.phase {
+            SwingPhase::READY => None,
+            SwingPhase::WINDING_UP => {
+                self.phase = SwingPhase::RECOVERING;
+                self.timer = self.stats.recovery_ticks;
+                Some(Swing { origin, facing: self.facing, stats: self.stats, swinger })
+            },
+            SwingPhase::RECOVERING => {
+                self.phase = SwingPhase::READY;
+                None
+            },
+        }
+    }
+}
+
+// whether a circle is inside the swing's reach and arc. anything overlapping the swinger gets
+// hit whichever way it's facing
+pub fn in_arc(swing: &Swing, center: Vector2f, radius: f32) -> bool {
+    let offset = center - swing.origin;
+    let distance = offset.length_sq().sqrt();
+
+    if distance - radius > swing.stats.reach {
+        return false;
+    }
+    if distance <= radius {
+        return true;
+    }
+
+    let half_arc = (swing.stats.arc / 2.0).to_radians();
+    let along = (offset.x * swing.facing.x + offset.y * swing.facing.y) / distance;
+    // the circle's edge can poke into the arc before its centre does
+    along >= (half_arc + (radius / distance).asin()).min(std::f32::consts::PI).cos()
+}
+
+// everything the swing catches. targets are (index, position, radius), anything that can't be
+// hit should already be left out
+pub fn resolve_swing(swing: &Swing, targets: impl Iterator<Item = (usize, Vector2f, f32)>) -> Vec<MeleeHit> {
+    targets
+        .filter(|(_, center, radius)| in_arc(swing, *center, *radius))
+        .map(|(target, center, _)| {
+            let away = center - swing.origin;
+            let direction = if away.length_sq() > 0.0 { away / away.length_sq().sqrt() } else { swing.facing };
+
+            MeleeHit {
+                target,
+                damage: Damage::new(swing.stats.damage, DamageKind::MELEE, Some(swing.origin)),
+                push: direction * swing.stats.knockback,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STATS: SwingStats = SwingStats {
+        windup_ticks: 3,
+        recovery_ticks: 2,
+        reach: 30.0,
+        arc: 90.0,
+        damage: 20.0,
+        knockback: 10.0,
+        stamina_cost: 0.0,
+    };
+
+    fn swing() -> Swing {
+        Swing { origin: Vector2f::new(0.0, 0.0), facing: Vector2f::new(1.0, 0.0), stats: STATS, swinger: EntityType::PLAYER }
+    }
+
+    #[test]
+    fn swings_land_after_winding_up_and_recover_before_the_next() {
+        let mut melee = Melee::new(STATS);
+        let origin = Vector2f::new(5.0, 5.0);
+
+        assert!(!melee.start(Vector2f::new(0.0, 0.0)));
+        assert!(melee.start(Vector2f::new(0.0, -4.0)));
+        assert!(!melee.start(Vector2f::new(1.0, 0.0)));
+
+        assert_eq!(melee.update(origin, EntityType::ZOMBIE), None);
+        assert_eq!(melee.update(origin, EntityType::ZOMBIE), None);
+        let swing = melee.update(origin, EntityType::ZOMBIE).unwrap();
+        assert_eq!((swing.origin, swing.facing), (origin, Vector2f::new(0.0, -1.0)));
+
+        // still recovering
+        assert!(!melee.start(Vector2f::new(1.0, 0.0)));
+        assert_eq!(melee.update(origin, EntityType::ZOMBIE), None);
+        assert_eq!(melee.update(origin, EntityType::ZOMBIE), None);
+        assert!(melee.is_ready());
+        assert!(melee.start(Vector2f::new(1.0, 0.0)));
+    }
+
+    #[test]
+    fn only_things_inside_the_arc_get_hit() {
+        let swing = swing();
+
+        assert!(in_arc(&swing, Vector2f::new(35.0, 0.0), 10.0));
+        assert!(!in_arc(&swing, Vector2f::new(45.0, 0.0), 10.0));
+        // 60 degrees off to the side, outside a 90 degree arc unless it's big enough to poke in
+        let side = Vector2f::new(60f32.to_radians().cos(), 60f32.to_radians().sin()) * 20.0;
+        assert!(!in_arc(&swing, side, 1.0));
+        assert!(in_arc(&swing, side, 8.0));
+        // behind it, but standing on top of it
+        assert!(in_arc(&swing, Vector2f::new(-4.0, 0.0), 10.0));
+        assert!(!in_arc(&swing, Vector2f::new(-20.0, 0.0), 10.0));
+    }
+
+    #[test]
+    fn hits_shove_targets_away_from_the_swinger() {
+        let targets = [(1, Vector2f::new(20.0, 0.0), 10.0), (2, Vector2f::new(0.0, 20.0), 10.0), (4, Vector2f::new(0.0, 0.0), 10.0)];
+        let hits = resolve_swing(&swing(), targets.iter().copied());
+
+        assert_eq!(hits.iter().map(|hit| hit.target).collect::<Vec<_>>(), vec![1, 4]);
+        assert_eq!(hits[0].push, Vector2f::new(10.0, 0.0));
+        assert_eq!(hits[0].damage, Damage::new(20.0, DamageKind::MELEE, Some(Vector2f::new(0.0, 0.0))));
+        // nowhere to be shoved away from, so it goes the way the swing was going
+        assert_eq!(hits[1].push, Vector2f::new(10.0, 0.0));
+    }
+}
@@ -0,0 +1,445 @@
+use std::{cmp::Ordering, collections::{BinaryHeap, HashMap}, f32::consts::SQRT_2};
+
+use sfml::system::Vector2f;
+
+use crate::{
+    entities::entity::{Entity, HITBOX_SIZE},
+    map::{tiles_on_line, world_to_tile, Map, TILE_SIZE},
+};
+
+// tiles a single search may look at before it gives up, keeps a search for an unreachable
+// goal from flooding the whole loaded map
+pub const MAX_SEARCH_NODES: usize = 2048;
+// tiles searched per tick over all entities, spread round robin so a horde takes turns
+pub const DEFAULT_PATH_BUDGET: usize = 6000;
+// a jump in open ground stops after this many tiles so it can't run forever
+const MAX_JUMP: i32 = 48;
+// jump point search reads far more tiles than it ever queues, so scanned tiles get their own
+// looser limit instead of counting against max_nodes
+const SCANS_PER_NODE: usize = 64;
+
+pub const DIRECTIONS: [(i32, i32); 8] = [
+    (1, 0), (-1, 0), (0, 1), (0, -1),
+    (1, 1), (1, -1), (-1, 1), (-1, -1),
+];
+
+#[allow(non_camel_case_types)]
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum SearchMode {
+    // plain A* over every neighbouring tile
+    ASTAR,
+    // same paths, but straight runs are skipped over instead of queued tile by tile
+    JUMP_POINT,
+}
+
+#[derive(PartialEq)]
+pub struct Node {
+    pub cost: f32,
+    pub tile: (i32, i32),
+}
+
+impl Eq for Node {}
+
+// BinaryHeap is a max heap, so cheaper nodes have to compare as bigger
+impl Ord for Node {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost)
+    }
+}
+
+impl PartialOrd for Node {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// 8 way distance on an empty grid
+pub fn octile(from: (i32, i32), to: (i32, i32)) -> f32 {
+    let dx = (from.0 - to.0).abs() as f32;
+    let dy = (from.1 - to.1).abs() as f32;
+
+    dx.max(dy) + (SQRT_2 - 1.0) * dx.min(dy)
+}
+
+// diagonal steps need both tiles beside them free, nothing squeezes between two walls'
+// corners
+pub fn can_step(tile: (i32, i32), direction: (i32, i32), is_blocked: &impl Fn(i32, i32) -> bool) -> bool {
+    let (x, y) = tile;
+    let (dx, dy) = direction;
+
+    !is_blocked(x + dx, y + dy) && (dx == 0 || dy == 0 || (!is_blocked(x + dx, y) && !is_blocked(x, y + dy)))
+}
+
+// follows direction from tile until something worth stopping at: the goal, a tile where a
+// wall ends and a new way opens up, or, going diagonally, a tile a straight jump finds
+// something from. a jump that runs into MAX_JUMP stops there as a plain node when stop_at_cap
+// is set and finds nothing otherwise, so the straight scans off a diagonal don't turn every
+// diagonal step in open ground into a jump point. scanned counts every tile looked at
+fn jump(
+    tile: (i32, i32),
+    direction: (i32, i32),
+    goal: (i32, i32),
+    stop_at_cap: bool,
+    is_blocked: &impl Fn(i32, i32) -> bool,
+    scanned: &mut usize,
+) -> Option<(i32, i32)> {
+    let (dx, dy) = direction;
+    let (mut x, mut y) = tile;
+
+    for _ in 0..MAX_JUMP {
+        if !can_step((x, y), direction, is_blocked) {
+            return None;
+        }
+        x += dx;
+        y += dy;
+        *scanned += 1;
+
+        if (x, y) == goal {
+            return Some((x, y));
+        }
+
+        if dx != 0 && dy != 0 {
+            if jump((x, y), (dx, 0), goal, false, is_blocked, scanned).is_some() || jump((x, y), (0, dy), goal, false, is_blocked, scanned).is_some() {
+                return Some((x, y));
+            }
+        } else if dx != 0 {
+            if [-1, 1].iter().any(|side| !is_blocked(x, y + side) && is_blocked(x - dx, y + side)) {
+                return Some((x, y));
+            }
+        } else if [-1, 1].iter().any(|side| !is_blocked(x + side, y) && is_blocked(x + side, y - dy)) {
+            return Some((x, y));
+        }
+    }
+
+    stop_at_cap.then_some((x, y))
+}
+
+// shortest 8 way path between two world tiles, start and goal included. in jump point mode
+// only the tiles where the path turns are returned, the straight runs between them are clear.
+// also returns how many tiles were searched from, None once that passes max_nodes
+pub fn find_path(
+    start: (i32, i32),
+    goal: (i32, i32),
+    mode: SearchMode,
+    max_nodes: usize,
+    is_blocked: impl Fn(i32, i32) -> bool,
+) -> (Option<Vec<(i32, i32)>>, usize) {
+    if is_blocked(goal.0, goal.1) {
+        return (None, 0);
+    }
+
+    let mut open = BinaryHeap::from([Node { cost: octile(start, goal), tile: start }]);
+    let mut costs = HashMap::from([(start, 0.0)]);
+    let mut came_from: HashMap<(i32, i32), (i32, i32)> = HashMap::new();
+    let mut visited = 0;
+    let mut scanned = 0;
+
+    while let Some(Node { tile, cost }) = open.pop() {
+        if tile == goal {
+            let mut path = vec![goal];
+            while let Some(previous) = came_from.get(path.last().unwrap()) {
+                path.push(*previous);
+            }
+            path.reverse();
+
+            return (Some(path), visited);
+        }
+
+        // stale entry, this tile was already reached cheaper
+        if cost > costs[&tile] + octile(tile, goal) + 0.001 {
+            continue;
+        }
+
+        visited += 1;
+        if visited > max_nodes || scanned > max_nodes * SCANS_PER_NODE {
+            return (None, visited);
+        }
+
+        for direction in DIRECTIONS {
+            if !can_step(tile, direction, &is_blocked) {
+                continue;
+            }
+
+            let next = match mode {
+                SearchMode::ASTAR => (tile.0 + direction.0, tile.1 + direction.1),
+                SearchMode::JUMP_POINT => match jump(tile, direction, goal, true, &is_blocked, &mut scanned) {
+                    Some(next) => next,
+                    None => continue,
+                },
+            };
+
+            let next_cost = costs[&tile] + octile(tile, next);
+            if !matches!(costs.get(&next), Some(known) if *known <= next_cost) {
+                costs.insert(next, next_cost);
+                came_from.insert(next, tile);
+                open.push(Node { cost: next_cost + octile(next, goal), tile: next });
+            }
+        }
+    }
+
+    (None, visited)
+}
+
+// true if a box clearance wide can slide along the line without touching a blocked tile.
+// checked along both of the box's edges as well as through its middle
+fn is_clear(from: Vector2f, to: Vector2f, clearance: f32, is_blocked: &impl Fn(i32, i32) -> bool) -> bool {
+    let delta = to - from;
+    let length = delta.length_sq().sqrt();
+    if length == 0.0 {
+        return true;
+    }
+
+    let side = Vector2f::new(-delta.y, delta.x) / length * (clearance / 2.0);
+
+    [Vector2f::new(0.0, 0.0), side, -side].iter().all(|offset| {
+        tiles_on_line(from + *offset, to + *offset)
+            .into_iter()
+            .all(|(tile_x, tile_y)| !is_blocked(tile_x, tile_y))
+    })
+}
+
+// turns a tile path into world space waypoints, dropping every one that can be walked past in
+// a straight line. the start tile is left out, whoever asked is already there
+pub fn smooth_path(path: &[(i32, i32)], clearance: f32, is_blocked: impl Fn(i32, i32) -> bool) -> Vec<Vector2f> {
+    let centers: Vec<Vector2f> = path
+        .iter()
+        .map(|(tile_x, tile_y)| {
+            Vector2f::new(
+                (tile_x * TILE_SIZE + TILE_SIZE / 2) as f32,
+                (tile_y * TILE_SIZE + TILE_SIZE / 2) as f32,
+            )
+        })
+        .collect();
+
+    let mut waypoints = Vec::new();
+    let mut anchor = 0;
+
+    while anchor + 1 < centers.len() {
+        let mut furthest = anchor + 1;
+        for next in (anchor + 2..centers.len()).rev() {
+            if is_clear(centers[anchor], centers[next], clearance, &is_blocked) {
+                furthest = next;
+                break;
+            }
+        }
+
+        waypoints.push(centers[furthest]);
+        anchor = furthest;
+    }
+
+    waypoints
+}
+
+// hands out paths to entities that ask for one, taking turns so every zombie gets its search
+// eventually and no single tick does more than budget tiles of searching
+pub struct PathPlanner {
+    pub budget: usize,
+    pub mode: SearchMode,
+    // entity to start with next tick
+    next: usize,
+}
+
+impl PathPlanner {
+    pub fn new(budget: usize, mode: SearchMode) -> Self {
+        PathPlanner { budget, mode, next: 0 }
+    }
+
+    pub fn plan(&mut self, entities: &mut [Box<dyn Entity>], map: &Map) {
+        if entities.is_empty() {
+            return;
+        }
+
+        let mut spent = 0;
+        let start = self.next % entities.len();
+
+        for offset in 0..entities.len() {
+            let index = (start + offset) % entities.len();
+            let entity = &mut entities[index];
+
+            let Some(target) = entity.wants_path() else {
+                continue;
+            };
+
+            if spent >= self.budget {
+                // out of budget, this one goes first next tick
+                self.next = index;
+                return;
+            }
+
+            let can_swim = entity.can_swim();
+            let is_blocked = |tile_x, tile_y| map.is_blocked(tile_x, tile_y, can_swim);
+
+            let limit = MAX_SEARCH_NODES.min(self.budget - spent);
+            let (path, visited) = find_path(
+                world_to_tile(entity.get_position()),
+                world_to_tile(target),
+                self.mode,
+                limit,
+                is_blocked,
+            );
+            spent += visited;
+
+            // cut short by the budget rather than its own limit, so it gets another go next tick
+            if path.is_none() && limit < MAX_SEARCH_NODES && visited > limit {
+                self.next = index;
+                return;
+            }
+
+            // no path means walking straight at it until the next try
+            let waypoints = path
+                .map(|path| smooth_path(&path, HITBOX_SIZE, is_blocked))
+                .unwrap_or_default();
+            entity.set_path(waypoints);
+        }
+
+        self.next = start + 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    // # is a wall, everything outside the picture is too
+    fn grid(rows: &[&str]) -> impl Fn(i32, i32) -> bool {
+        let walls: HashSet<(i32, i32)> = rows
+            .iter()
+            .enumerate()
+            .flat_map(|(y, row)| row.chars().enumerate().filter(|(_, tile)| *tile == '#').map(move |(x, _)| (x as i32, y as i32)))
+            .collect();
+        let (width, height) = (rows[0].len() as i32, rows.len() as i32);
+
+        move |x, y| x < 0 || y < 0 || x >= width || y >= height || walls.contains(&(x, y))
+    }
+
+    fn path_cost(path: &[(i32, i32)]) -> f32 {
+        path.windows(2).map(|pair| octile(pair[0], pair[1])).sum()
+    }
+
+    #[test]
+    fn paths_go_around_walls() {
+        let is_blocked = grid(&[
+            "..........",
+            "....#.....",
+            "....#.....",
+            "....#.....",
+            "....#.....",
+            "..........",
+        ]);
+
+        let (path, _) = find_path((1, 3), (8, 3), SearchMode::ASTAR, MAX_SEARCH_NODES, &is_blocked);
+        let path = path.unwrap();
+
+        assert_eq!(path.first(), Some(&(1, 3)));
+        assert_eq!(path.last(), Some(&(8, 3)));
+        assert!(path.iter().all(|(x, y)| !is_blocked(*x, *y)));
+        // around the bottom of the wall, not over the longer top
+        assert!(path.contains(&(4, 5)));
+    }
+
+    #[test]
+    fn paths_do_not_cut_corners() {
+        let is_blocked = grid(&[
+            ".#",
+            "#.",
+        ]);
+
+        // the only way across squeezes between two walls' corners
+        for mode in [SearchMode::ASTAR, SearchMode::JUMP_POINT] {
+            let (path, _) = find_path((0, 0), (1, 1), mode, MAX_SEARCH_NODES, &is_blocked);
+            assert_eq!(path, None);
+        }
+    }
+
+    #[test]
+    fn unreachable_goals_give_up() {
+        let is_blocked = |x: i32, y: i32| (x, y) != (0, 0) && x.abs() <= 1 && y.abs() <= 1;
+
+        // walled in on an infinite grid, the node limit is the only thing that ends this
+        let (path, visited) = find_path((10, 10), (0, 0), SearchMode::ASTAR, 500, is_blocked);
+        assert_eq!(path, None);
+        assert!(visited <= 501);
+    }
+
+    #[test]
+    fn jump_point_paths_cost_the_same_as_astar() {
+        let mut rng = StdRng::seed_from_u64(5);
+
+        for _ in 0..40 {
+            let rows: Vec<String> = (0..24)
+                .map(|_| (0..32).map(|_| if rng.gen_ratio(1, 4) { '#' } else { '.' }).collect())
+                .collect();
+            let rows: Vec<&str> = rows.iter().map(|row| row.as_str()).collect();
+            let is_blocked = grid(&rows);
+
+            let start = (rng.gen_range(0..32), rng.gen_range(0..24));
+            let goal = (rng.gen_range(0..32), rng.gen_range(0..24));
+            if is_blocked(start.0, start.1) {
+                continue;
+            }
+
+            let (astar, _) = find_path(start, goal, SearchMode::ASTAR, 100_000, &is_blocked);
+            let (jump_point, _) = find_path(start, goal, SearchMode::JUMP_POINT, 100_000, &is_blocked);
+
+            assert_eq!(astar.is_some(), jump_point.is_some());
+            if let (Some(astar), Some(jump_point)) = (astar, jump_point) {
+                assert!((path_cost(&astar) - path_cost(&jump_point)).abs() < 0.001);
+            }
+        }
+    }
+
+    #[test]
+    fn jump_point_searches_no_more_than_astar_in_the_open() {
+        let rows = vec![".".repeat(64); 64];
+        let rows: Vec<&str> = rows.iter().map(|row| row.as_str()).collect();
+        let is_blocked = grid(&rows);
+
+        for (start, goal) in [((0, 0), (63, 63)), ((5, 40), (60, 3)), ((32, 0), (31, 63)), ((63, 10), (0, 20))] {
+            let (astar, astar_visited) = find_path(start, goal, SearchMode::ASTAR, MAX_SEARCH_NODES, &is_blocked);
+            let (jump_point, jump_point_visited) = find_path(start, goal, SearchMode::JUMP_POINT, MAX_SEARCH_NODES, &is_blocked);
+
+            assert!(astar.is_some() && jump_point.is_some());
+            assert!(jump_point_visited <= astar_visited, "{} > {}", jump_point_visited, astar_visited);
+        }
+    }
+
+    #[test]
+    fn long_diagonals_fit_in_the_default_limit() {
+        // open ground in every direction, nothing for a straight scan to ever run into
+        let is_blocked = |_: i32, _: i32| false;
+
+        let (path, visited) = find_path((0, 0), (300, 220), SearchMode::JUMP_POINT, MAX_SEARCH_NODES, is_blocked);
+        let path = path.unwrap();
+
+        assert!((path_cost(&path) - octile((0, 0), (300, 220))).abs() < 0.001);
+        assert!(visited < 50);
+    }
+
+    #[test]
+    fn smoothing_keeps_only_the_corners() {
+        let is_blocked = grid(&[
+            "........",
+            "........",
+            "######..",
+            "........",
+            "........",
+        ]);
+
+        let (path, _) = find_path((0, 0), (0, 4), SearchMode::ASTAR, MAX_SEARCH_NODES, &is_blocked);
+        let path = path.unwrap();
+        let waypoints = smooth_path(&path, HITBOX_SIZE, &is_blocked);
+
+        assert!(waypoints.len() < path.len() - 1);
+        assert_eq!(waypoints.last(), Some(&Vector2f::new(16.0, 4.0 * 32.0 + 16.0)));
+
+        // every leg between waypoints is walkable
+        let start = Vector2f::new(16.0, 16.0);
+        for (from, to) in std::iter::once(start).chain(waypoints.iter().copied()).zip(waypoints.iter().copied()) {
+            assert!(is_clear(from, to, HITBOX_SIZE, &is_blocked));
+        }
+    }
+}
@@ -0,0 +1,52 @@
+use sfml::graphics::Color;
+
+// decoration placed by world generation, one per tile at most
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PropKind {
+    TREE = 0,
+    BUSH = 1,
+    ROCK = 2,
+}
+
+impl PropKind {
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            0 => Some(PropKind::TREE),
+            1 => Some(PropKind::BUSH),
+            2 => Some(PropKind::ROCK),
+            _ => None,
+        }
+    }
+
+    // solid props block movement like a non-walkable tile
+    pub fn is_solid(&self) -> bool {
+        match self {
+            PropKind::TREE | PropKind::ROCK => true,
+            PropKind::BUSH => false,
+        }
+    }
+
+    pub fn radius(&self) -> f32 {
+        match self {
+            PropKind::TREE => 14.0,
+            PropKind::BUSH => 10.0,
+            PropKind::ROCK => 9.0,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            PropKind::TREE => Color::rgb(34, 77, 34),
+            PropKind::BUSH => Color::rgb(70, 110, 45),
+            PropKind::ROCK => Color::rgb(120, 120, 115),
+        }
+    }
+}
+
+// tile_x and tile_y are the prop's tile inside its chunk
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Prop {
+    pub kind: PropKind,
+    pub tile_x: u8,
+    pub tile_y: u8,
+}
@@ -0,0 +1,89 @@
+use sfml::system::Vector2f;
+
+// how far each kind of sound carries in the open, in pixels
+pub const GUNSHOT_LOUDNESS: f32 = 640.0;
+pub const DOOR_LOUDNESS: f32 = 192.0;
+pub const SPRINT_LOUDNESS: f32 = 160.0;
+
+// every wall in the way lets this much of a sound through
+const WALL_MUFFLING: f32 = 0.4;
+// anything quieter than this by the time it arrives isn't noticed
+const HEARING_THRESHOLD: f32 = 0.05;
+
+// something that made a sound this tick
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SoundEvent {
+    pub position: Vector2f,
+    pub loudness: f32,
+}
+
+impl SoundEvent {
+    pub fn new(position: Vector2f, loudness: f32) -> Self {
+        SoundEvent { position, loudness }
+    }
+
+    // how loud it is at the listener, from 1 right on top of it down to 0 once it's out of
+    // range. fades linearly with distance and drops off sharply for every wall in between
+    pub fn volume_at(&self, listener: Vector2f, walls_between: usize) -> f32 {
+        let delta = listener - self.position;
+        let distance = delta.length_sq().sqrt();
+
+        let open_air = (1.0 - distance / self.loudness).max(0.0);
+        open_air * WALL_MUFFLING.powi(walls_between as i32)
+    }
+
+    pub fn in_range(&self, listener: Vector2f) -> bool {
+        (listener - self.position).length_sq() < self.loudness * self.loudness
+    }
+}
+
+// where the loudest sound the listener can make out came from. walls_between is only asked
+// about sounds that are in range, it's the expensive part
+pub fn loudest_heard(sounds: &[SoundEvent], listener: Vector2f, walls_between: impl Fn(Vector2f, Vector2f) -> usize) -> Option<Vector2f> {
+    sounds
+        .iter()
+        .filter(|sound| sound.in_range(listener))
+        .map(|sound| (sound.position, sound.volume_at(listener, walls_between(sound.position, listener))))
+        .filter(|(_, volume)| *volume >= HEARING_THRESHOLD)
+        .max_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(position, _)| position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: Vector2f = Vector2f::new(0.0, 0.0);
+
+    #[test]
+    fn sounds_fade_with_distance_and_walls() {
+        let shot = SoundEvent::new(ORIGIN, GUNSHOT_LOUDNESS);
+
+        assert_eq!(shot.volume_at(ORIGIN, 0), 1.0);
+        assert!(shot.volume_at(Vector2f::new(100.0, 0.0), 0) > shot.volume_at(Vector2f::new(300.0, 0.0), 0));
+        assert_eq!(shot.volume_at(Vector2f::new(GUNSHOT_LOUDNESS + 1.0, 0.0), 0), 0.0);
+
+        let near = Vector2f::new(100.0, 0.0);
+        assert!(shot.volume_at(near, 1) < shot.volume_at(near, 0));
+        assert!(shot.volume_at(near, 2) < shot.volume_at(near, 1));
+    }
+
+    #[test]
+    fn the_loudest_sound_in_range_wins() {
+        let listener = Vector2f::new(150.0, 0.0);
+        let no_walls = |_, _| 0;
+
+        let door = SoundEvent::new(Vector2f::new(100.0, 0.0), DOOR_LOUDNESS);
+        let shot = SoundEvent::new(ORIGIN, GUNSHOT_LOUDNESS);
+        let far_steps = SoundEvent::new(Vector2f::new(1000.0, 0.0), SPRINT_LOUDNESS);
+
+        assert_eq!(loudest_heard(&[door, shot, far_steps], listener, no_walls), Some(ORIGIN));
+        assert_eq!(loudest_heard(&[door, far_steps], listener, no_walls), Some(door.position));
+        assert_eq!(loudest_heard(&[far_steps], listener, no_walls), None);
+
+        // the shot came from the other side of a thick wall, the door didn't
+        let walled_off = |from: Vector2f, _| if from == ORIGIN { 4 } else { 0 };
+        assert_eq!(loudest_heard(&[door, shot], listener, walled_off), Some(door.position));
+        assert_eq!(loudest_heard(&[shot], listener, walled_off), None);
+    }
+}
@@ -0,0 +1,164 @@
+use std::collections::HashMap;
+
+use sfml::{graphics::FloatRect, system::Vector2f};
+
+// two tiles, about the size of the biggest query anything makes every tick
+pub const DEFAULT_CELL_SIZE: f32 = 64.0;
+
+// uniform grid of entity positions, rebuilt once per tick. queries hand back indices into
+// whatever list it was built from, so they're only valid until that list changes
+pub struct SpatialHash {
+    pub cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<usize>>,
+    positions: Vec<Vector2f>,
+}
+
+impl SpatialHash {
+    pub fn new(cell_size: f32) -> Self {
+        SpatialHash {
+            cell_size,
+            cells: HashMap::new(),
+            positions: Vec::new(),
+        }
+    }
+
+    // empty cells are dropped first so the map doesn't keep every cell anything ever walked
+    // through, the rest keep their allocations for this tick
+    pub fn rebuild(&mut self, positions: impl Iterator<Item = Vector2f>) {
+        self.cells.retain(|_, cell| !cell.is_empty());
+        for cell in self.cells.values_mut() {
+            cell.clear();
+        }
+
+        self.positions.clear();
+        self.positions.extend(positions);
+
+        for (index, position) in self.positions.iter().enumerate() {
+            self.cells.entry(self.cell_of(*position)).or_default().push(index);
+        }
+    }
+
+    fn cell_of(&self, position: Vector2f) -> (i32, i32) {
+        (
+            (position.x / self.cell_size).floor() as i32,
+            (position.y / self.cell_size).floor() as i32,
+        )
+    }
+
+    // every entity inside the rect, edges included
+    pub fn query_rect(&self, rect: FloatRect) -> Vec<usize> {
+        let first = self.cell_of(Vector2f::new(rect.left, rect.top));
+        let last = self.cell_of(Vector2f::new(rect.left + rect.width, rect.top + rect.height));
+
+        let mut found = Vec::new();
+
+        for x in first.0..=last.0 {
+            for y in first.1..=last.1 {
+                let Some(cell) = self.cells.get(&(x, y)) else {
+                    continue;
+                };
+
+                found.extend(cell.iter().copied().filter(|index| {
+                    let position = self.positions[*index];
+                    position.x >= rect.left
+                        && position.x <= rect.left + rect.width
+                        && position.y >= rect.top
+                        && position.y <= rect.top + rect.height
+                }));
+            }
+        }
+
+        found
+    }
+
+    // every entity within radius of center
+    pub fn query_radius(&self, center: Vector2f, radius: f32) -> Vec<usize> {
+        let bounds = FloatRect::new(center.x - radius, center.y - radius, radius * 2.0, radius * 2.0);
+
+        let mut found = self.query_rect(bounds);
+        found.retain(|index| (self.positions[*index] - center).length_sq() <= radius * radius);
+        found
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Instant;
+
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    use super::*;
+
+    fn random_positions(count: usize, spread: f32, seed: u64) -> Vec<Vector2f> {
+        let mut rng = StdRng::seed_from_u64(seed);
+
+        (0..count)
+            .map(|_| Vector2f::new(rng.gen_range(-spread..spread), rng.gen_range(-spread..spread)))
+            .collect()
+    }
+
+    fn brute_force_radius(positions: &[Vector2f], center: Vector2f, radius: f32) -> Vec<usize> {
+        (0..positions.len())
+            .filter(|index| (positions[*index] - center).length_sq() <= radius * radius)
+            .collect()
+    }
+
+    #[test]
+    fn queries_match_brute_force() {
+        let positions = random_positions(500, 600.0, 7);
+        let mut hash = SpatialHash::new(DEFAULT_CELL_SIZE);
+        hash.rebuild(positions.iter().copied());
+
+        for (center, radius) in [
+            (Vector2f::new(0.0, 0.0), 100.0),
+            (Vector2f::new(-350.5, 220.0), 37.0),
+            (Vector2f::new(590.0, -590.0), 250.0),
+        ] {
+            let mut found = hash.query_radius(center, radius);
+            found.sort();
+            assert_eq!(found, brute_force_radius(&positions, center, radius));
+        }
+
+        let rect = FloatRect::new(-200.0, -50.0, 130.0, 400.0);
+        let mut found = hash.query_rect(rect);
+        found.sort();
+        let expected: Vec<usize> = (0..positions.len())
+            .filter(|index| {
+                let position = positions[*index];
+                position.x >= rect.left && position.x <= rect.left + rect.width && position.y >= rect.top && position.y <= rect.top + rect.height
+            })
+            .collect();
+        assert_eq!(found, expected);
+    }
+
+    // cargo test spatial_hash_benchmark --release -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn spatial_hash_benchmark() {
+        const ZOMBIES: usize = 10_000;
+        const NEIGHBOUR_RADIUS: f32 = 32.0;
+
+        // roughly a screen's worth of chunks around the player, crowded
+        let positions = random_positions(ZOMBIES, 2000.0, 1);
+
+        let start = Instant::now();
+        let mut hash = SpatialHash::new(DEFAULT_CELL_SIZE);
+        hash.rebuild(positions.iter().copied());
+        let rebuild_time = start.elapsed();
+
+        let start = Instant::now();
+        let hashed_pairs: usize = positions.iter().map(|position| hash.query_radius(*position, NEIGHBOUR_RADIUS).len()).sum();
+        let hashed_time = start.elapsed();
+
+        let start = Instant::now();
+        let brute_force_pairs: usize = positions.iter().map(|position| brute_force_radius(&positions, *position, NEIGHBOUR_RADIUS).len()).sum();
+        let brute_force_time = start.elapsed();
+
+        println!("{} zombies, every one asking for neighbours within {} px", ZOMBIES, NEIGHBOUR_RADIUS);
+        println!("{:>12} {:?}", "rebuild", rebuild_time);
+        println!("{:>12} {:?}", "hashed", hashed_time);
+        println!("{:>12} {:?}", "brute force", brute_force_time);
+
+        assert_eq!(hashed_pairs, brute_force_pairs);
+    }
+}
@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use sfml::system::Vector2f;
+
+use crate::{
+    entities::zombie::ZombieKind,
+    generation::{
+        biome::{pick_weighted, Biome},
+        WorldGenerator,
+    },
+    map::{world_to_chunk, CHUNK_SIZE, TILE_SIZE},
+};
+
+// most zombies alive at once, anywhere
+pub const DEFAULT_POPULATION_CAP: usize = 48;
+// zombies further than this from the player are removed, a bit past where chunks unload
+pub const DESPAWN_DISTANCE: f32 = 1400.0;
+
+// ticks between spawn passes
+const SPAWN_INTERVAL_TICKS: u64 = 30;
+// chance a chunk that's short of zombies gets one of them back on a pass, so they trickle in
+const SPAWN_CHANCE: f64 = 0.2;
+// no chunk holds more than this whatever its biome says
+const MAX_ZOMBIES_PER_CHUNK: usize = 4;
+// random tiles tried per spawn before giving up until the next pass
+const SPAWN_ATTEMPTS: u32 = 4;
+
+const TARGET_SALT: u64 = 0x7461_7267_6574_7321;
+const SPAWN_SALT: u64 = 0x7370_6177_6e69_6e67;
+
+// a zombie the director wants added to the world
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Spawn {
+    pub position: Vector2f,
+    pub kind: ZombieKind,
+    // for the zombie's own randomness, so it behaves the same every time too
+    pub seed: u64,
+}
+
+// keeps loaded chunks populated according to their biome's spawn table. everything it does
+// comes from the world seed and its tick count, the same world and the same ticks always give
+// the same zombies in the same places
+pub struct SpawnDirector {
+    pub generator: WorldGenerator,
+    pub population_cap: usize,
+    tick: u64,
+    next_pass: u64,
+}
+
+impl SpawnDirector {
+    pub fn new(generator: WorldGenerator, population_cap: usize) -> Self {
+        SpawnDirector {
+            generator,
+            population_cap,
+            tick: 0,
+            next_pass: SPAWN_INTERVAL_TICKS,
+        }
+    }
+
+    // how many zombies a chunk holds when it's full. the fraction of the biome's average is
+    // rolled once per chunk, so over many chunks it comes out to the average
+    pub fn chunk_target(&self, chunk: (i32, i32), biome: Biome) -> usize {
+        let average = biome.spawn_table().zombies_per_chunk.max(0.0) as f64;
+        let roll = self.generator.tile_hash(chunk.0, chunk.1, TARGET_SALT);
+
+        let target = average.floor() as usize + usize::from(roll < average.fract());
+        target.min(MAX_ZOMBIES_PER_CHUNK)
+    }
+
+    // call once per tick. loaded is every loaded chunk with its biome, zombies where every
+    // zombie is right now. is_visible is anything the player could watch popping into existence
+    pub fn update(
+        &mut self,
+        loaded: &[((i32, i32), Biome)],
+        zombies: &[Vector2f],
+        is_blocked: impl Fn(i32, i32) -> bool,
+        is_visible: impl Fn(Vector2f) -> bool,
+    ) -> Vec<Spawn> {
+        self.tick += 1;
+
+        let mut spawns = Vec::new();
+        if self.tick < self.next_pass || zombies.len() >= self.population_cap {
+            return spawns;
+        }
+        self.next_pass = self.tick + SPAWN_INTERVAL_TICKS;
+
+        let mut population: HashMap<(i32, i32), usize> = HashMap::new();
+        for zombie in zombies {
+            *population.entry(world_to_chunk(*zombie)).or_default() += 1;
+        }
+
+        // loaded chunks come out of a hash map, sorting them keeps the rolls in the same order
+        let mut loaded = loaded.to_vec();
+        loaded.sort_by_key(|(chunk, _)| *chunk);
+
+        for (chunk, biome) in loaded {
+            if zombies.len() + spawns.len() >= self.population_cap {
+                break;
+            }
+            if population.get(&chunk).copied().unwrap_or(0) >= self.chunk_target(chunk, biome) {
+                continue;
+            }
+
+            let mut rng = StdRng::seed_from_u64(self.generator.cell_seed(chunk.0, chunk.1, SPAWN_SALT ^ self.tick));
+            if !rng.gen_bool(SPAWN_CHANCE) {
+                continue;
+            }
+
+            for _ in 0..SPAWN_ATTEMPTS {
+                let tile_x = chunk.0 * CHUNK_SIZE as i32 + rng.gen_range(0..CHUNK_SIZE as i32);
+                let tile_y = chunk.1 * CHUNK_SIZE as i32 + rng.gen_range(0..CHUNK_SIZE as i32);
+                let position = Vector2f::new(
+                    (tile_x * TILE_SIZE + TILE_SIZE / 2) as f32,
+                    (tile_y * TILE_SIZE + TILE_SIZE / 2) as f32,
+                );
+
+                if is_blocked(tile_x, tile_y) || is_visible(position) {
+                    continue;
+                }
+
+                let kind = pick_weighted(biome.spawn_table().kinds, rng.gen());
+                spawns.push(Spawn { position, kind, seed: rng.gen() });
+                break;
+            }
+        }
+
+        spawns
+    }
+
+    pub fn should_despawn(player: Vector2f, zombie: Vector2f) -> bool {
+        (zombie - player).length_sq() > DESPAWN_DISTANCE * DESPAWN_DISTANCE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::map::world_to_tile;
+
+    use super::*;
+
+    fn loaded_around_origin(biome: Biome) -> Vec<((i32, i32), Biome)> {
+        let mut loaded = Vec::new();
+        for x in -4..=4 {
+            for y in -4..=4 {
+                loaded.push(((x, y), biome));
+            }
+        }
+        loaded
+    }
+
+    // a director run for a while with everything it spawns kept alive
+    fn run(seed: u32, ticks: u32, cap: usize, is_visible: impl Fn(Vector2f) -> bool) -> Vec<Spawn> {
+        let mut director = SpawnDirector::new(WorldGenerator::new(seed), cap);
+        let loaded = loaded_around_origin(Biome::TOWN_OUTSKIRTS);
+
+        let mut spawned: Vec<Spawn> = Vec::new();
+        for _ in 0..ticks {
+            let zombies: Vec<Vector2f> = spawned.iter().map(|spawn| spawn.position).collect();
+            spawned.extend(director.update(&loaded, &zombies, |tile_x, _| tile_x == 0, &is_visible));
+        }
+        spawned
+    }
+
+    #[test]
+    fn same_seed_and_ticks_spawn_the_same_zombies() {
+        let nothing_visible = |_| false;
+
+        let first = run(1234, 600, 100, nothing_visible);
+        assert!(!first.is_empty());
+        assert_eq!(first, run(1234, 600, 100, nothing_visible));
+        assert_ne!(first, run(4321, 600, 100, nothing_visible));
+    }
+
+    #[test]
+    fn spawns_stay_out_of_sight_walls_and_caps() {
+        let on_screen = |position: Vector2f| position.x.abs() < 400.0 && position.y.abs() < 300.0;
+        let spawned = run(99, 3000, 100, on_screen);
+
+        let director = SpawnDirector::new(WorldGenerator::new(99), 100);
+        let mut population: HashMap<(i32, i32), usize> = HashMap::new();
+
+        for spawn in spawned.iter() {
+            assert!(!on_screen(spawn.position));
+            assert_ne!(world_to_tile(spawn.position).0, 0);
+            *population.entry(world_to_chunk(spawn.position)).or_default() += 1;
+        }
+        for (chunk, count) in population {
+            assert!(count <= director.chunk_target(chunk, Biome::TOWN_OUTSKIRTS));
+        }
+
+        assert_eq!(run(99, 3000, 5, |_| false).len(), 5);
+    }
+
+    #[test]
+    fn chunk_targets_average_out_to_the_spawn_table() {
+        let director = SpawnDirector::new(WorldGenerator::new(7), DEFAULT_POPULATION_CAP);
+
+        for biome in [Biome::FOREST, Biome::BEACH, Biome::TOWN_OUTSKIRTS] {
+            let total: usize = (0..2500).map(|index| director.chunk_target((index % 50, index / 50), biome)).sum();
+            let average = total as f32 / 2500.0;
+
+            assert!((average - biome.spawn_table().zombies_per_chunk).abs() < 0.05);
+        }
+    }
+
+    #[test]
+    fn only_far_away_zombies_despawn() {
+        let player = Vector2f::new(100.0, 100.0);
+
+        assert!(!SpawnDirector::should_despawn(player, Vector2f::new(600.0, 100.0)));
+        assert!(SpawnDirector::should_despawn(player, Vector2f::new(100.0, 100.0 + DESPAWN_DISTANCE + 1.0)));
+    }
+}
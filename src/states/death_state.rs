@@ -0,0 +1,74 @@
+use std::sync::Arc;
+
+use sfml::{graphics::{Color, RenderTarget, RenderWindow, Text, Transformable}, system::Vector2f, window::{mouse::Button, Key}};
+
+use crate::{assets::Assets, entities::health::DamageKind};
+use super::{game_state::GameState, state::State};
+
+// shown once the player dies, enter starts a new game
+pub struct DeathState {
+    // shared with the game it came from and the one it starts, nothing gets loaded again
+    pub assets: Arc<Assets>,
+    pub window_size: Vector2f,
+    pub cause: DamageKind,
+    // how long the player lasted, in ticks
+    pub survived_ticks: u64,
+    restart: bool,
+}
+
+impl DeathState {
+    pub fn new(assets: Arc<Assets>, window_size: Vector2f, cause: DamageKind, survived_ticks: u64) -> Self {
+        DeathState {
+            assets,
+            window_size,
+            cause,
+            survived_ticks,
+            restart: false,
+        }
+    }
+}
+
+impl State for DeathState {
+    fn keypress_event(&mut self, key: Key) {
+        if key == Key::Enter {
+            self.restart = true;
+        }
+    }
+
+    fn keyrelease_event(&mut self, _: Key) {}
+
+    fn mouse_press_event(&mut self, _: Button) {}
+    fn mouse_release_event(&mut self, _: Button) {}
+    fn mouse_position_event(&mut self, _: Vector2f) {}
+
+    fn update(&mut self) {}
+
+    fn next_state(&mut self) -> Option<Box<dyn State>> {
+        if !self.restart {
+            return None;
+        }
+
+        Some(Box::new(GameState::new(self.assets.clone(), self.window_size)))
+    }
+
+    fn render(&mut self, window: &mut RenderWindow) {
+        let center = self.window_size / 2.0;
+        // ticks are 10ms
+        let seconds = self.survived_ticks / 100;
+
+        let lines = [
+            ("You died".to_string(), 48, Color::RED, -80.0),
+            (format!("Cause of death: {}", self.cause.name()), 24, Color::WHITE, 0.0),
+            (format!("Survived {}:{:02}", seconds / 60, seconds % 60), 24, Color::WHITE, 36.0),
+            ("Press enter to try again".to_string(), 20, Color::rgb(160, 160, 160), 110.0),
+        ];
+
+        for (string, size, color, offset) in lines {
+            let mut text = Text::new(&string, &self.assets.font, size);
+            text.set_fill_color(color);
+            // roughly centred, characters are about half as wide as they are tall
+            text.set_position(Vector2f::new(center.x - string.len() as f32 * size as f32 * 0.25, center.y + offset));
+            window.draw(&text);
+        }
+    }
+}
@@ -1,21 +1,41 @@
 use std::sync::Arc;
 
-use sfml::{graphics::RenderWindow, system::Vector2f, window::{mouse::Button, Key}};
+use sfml::{
+    graphics::{CircleShape, Color, FloatRect, PrimitiveType, RenderStates, RenderTarget, RenderWindow, Shape, Transformable, Vertex},
+    system::Vector2f,
+    window::{mouse::Button, Key},
+};
 
 use crate::{
     assets::Assets,
+    ballistics::{cast, Impact, ImpactKind, Projectile, Tracer, IMPACT_TICKS, TRACER_TICKS},
+    collision::{move_and_slide, separation, Contact},
     entities::{
-        entity::{Entity, EntityType},
+        brain::Perception,
+        corpse::Corpse,
+        entity::{Entity, EntityType, HITBOX_SIZE},
+        health::{resolve_damage, Damage, DamageEvent, DamageKind},
         player::Player,
         zombie::Zombie,
     },
-    map::Map,
+    flow_field::{FlowField, DEFAULT_FLOW_FIELD_BUDGET},
+    generation::biome::Biome,
+    map::{world_to_tile, Map, TILE_SIZE},
+    melee::{resolve_swing, Swing},
+    pathfinding::{PathPlanner, SearchMode, DEFAULT_PATH_BUDGET},
+    sound::{loudest_heard, SoundEvent, DOOR_LOUDNESS},
+    spatial_hash::{SpatialHash, DEFAULT_CELL_SIZE},
+    spawn_director::{SpawnDirector, DEFAULT_POPULATION_CAP},
     State,
 };
+use super::death_state::DeathState;
 
 #[derive(Clone)]
 pub struct MouseData {
+    // in window pixels
     pub position: Vector2f,
+    // what the cursor is over in the world, updated every tick
+    pub world_position: Vector2f,
     pub left_click: bool,
     pub right_click: bool,
 }
@@ -26,12 +46,41 @@ pub struct KeyboardData {
     pub a: bool,
     pub s: bool,
     pub d: bool,
+    pub crouch: bool,
+    pub sprint: bool,
+    // pressed this tick, cleared after every update
+    pub reload: bool,
+    pub chamber: bool,
+    pub unload: bool,
 }
 
+// furthest any zombie can see, in pixels
+const MAX_SIGHT_RANGE: f32 = 400.0;
+
 pub struct GameState {
     pub map: Map,
     pub entities: Vec<Box<dyn Entity>>,
     pub player_index: usize,
+    // every contact from the last update, for damage and melee to react to
+    pub contacts: Vec<Contact>,
+    // made this tick, heard by everything next tick
+    pub sounds: Vec<SoundEvent>,
+    // dealt at the end of the tick, indices match entities until then
+    pub damage_events: Vec<DamageEvent>,
+    // bullets still in flight, and what they left behind
+    pub projectiles: Vec<Projectile>,
+    pub impacts: Vec<Impact>,
+    pub tracers: Vec<Tracer>,
+    // what killed the player, the game is over once this is set
+    pub player_death: Option<DamageKind>,
+    pub ticks: u64,
+    // where every entity was after walking and knockback, bullets and separation both look
+    // things up in it. indices match entities
+    pub spatial_hash: SpatialHash,
+    pub path_planner: PathPlanner,
+    // shared directions to the player for everything chasing them
+    pub flow_field: FlowField,
+    pub spawn_director: SpawnDirector,
     pub keyboard_data: KeyboardData,
     pub mouse_data: MouseData,
     pub window_size: Vector2f,
@@ -40,14 +89,12 @@ pub struct GameState {
 }
 
 impl GameState {
-    pub fn new(assets: Assets, window_size: Vector2f) -> Self {
-        let assets = Arc::new(assets);
-
+    pub fn new(assets: Arc<Assets>, window_size: Vector2f) -> Self {
         let map = Map::new(assets.clone());
+        let spawn_director = SpawnDirector::new(map.generator.clone(), DEFAULT_POPULATION_CAP);
 
         let mut entities: Vec<Box<dyn Entity>> = Vec::new();
         entities.push(Box::new(Player::new(assets.clone())));
-        entities.push(Box::new(Zombie::new(assets.clone())));
         let player = 0;
 
         let camera_offset = Vector2f::new(0.0, 0.0);
@@ -57,10 +104,16 @@ impl GameState {
             a: false,
             s: false,
             d: false,
+            crouch: false,
+            sprint: false,
+            reload: false,
+            chamber: false,
+            unload: false,
         };
 
         let mouse_data = MouseData {
             position: Vector2f::new(0.0, 0.0),
+            world_position: Vector2f::new(0.0, 0.0),
             left_click: false,
             right_click: false,
         };
@@ -69,6 +122,18 @@ impl GameState {
             map,
             entities,
             player_index: player,
+            contacts: Vec::new(),
+            sounds: Vec::new(),
+            damage_events: Vec::new(),
+            projectiles: Vec::new(),
+            impacts: Vec::new(),
+            tracers: Vec::new(),
+            player_death: None,
+            ticks: 0,
+            spatial_hash: SpatialHash::new(DEFAULT_CELL_SIZE),
+            path_planner: PathPlanner::new(DEFAULT_PATH_BUDGET, SearchMode::JUMP_POINT),
+            flow_field: FlowField::new(DEFAULT_FLOW_FIELD_BUDGET),
+            spawn_director,
             keyboard_data,
             mouse_data,
             window_size,
@@ -76,6 +141,243 @@ impl GameState {
             assets: assets.clone(),
         }
     }
+
+    // pushes entities apart and lets the ones touching know about it
+    fn separate_entities(&mut self) {
+        let bodies: Vec<(Vector2f, f32, EntityType)> = self.entities
+            .iter()
+            .map(|entity| (entity.get_position(), entity.get_radius(), entity.get_type()))
+            .collect();
+        let (pushes, contacts) = separation(&bodies, |position, reach| self.spatial_hash.query_radius(position, reach));
+        self.contacts = contacts;
+
+        // pushes go through the same tile collision as walking, so crowds can't shove anyone
+        // into a wall
+        for (entity, push) in self.entities.iter_mut().zip(pushes) {
+            if push == Vector2f::new(0.0, 0.0) {
+                continue;
+            }
+
+            let can_swim = entity.can_swim();
+            let position = move_and_slide(
+                entity.get_position(),
+                Vector2f::new(HITBOX_SIZE, HITBOX_SIZE),
+                push,
+                |tile_x, tile_y| self.map.is_blocked(tile_x, tile_y, can_swim),
+            );
+            entity.set_position(position);
+        }
+
+        for contact in self.contacts.iter() {
+            self.entities[contact.first].on_contact(contact.second_type);
+            self.entities[contact.second].on_contact(contact.first_type);
+        }
+    }
+
+    // moves every bullet along, hitscan ones cover their whole range at once. whatever they
+    // hit takes damage for how far the bullet had come
+    fn step_projectiles(&mut self) {
+        for projectile in self.projectiles.iter_mut() {
+            let start = projectile.position;
+            let direction = projectile.shot.direction;
+            let length = projectile.step_length();
+
+            // only what's near the bullet's path this step, padded by the widest anything gets
+            // so something it clips the edge of still counts
+            let end = start + direction * length;
+            let padding = HITBOX_SIZE / 2.0;
+            let swept = FloatRect::new(
+                start.x.min(end.x) - padding,
+                start.y.min(end.y) - padding,
+                (end.x - start.x).abs() + padding * 2.0,
+                (end.y - start.y).abs() + padding * 2.0,
+            );
+            let targets = self.spatial_hash
+                .query_rect(swept)
+                .into_iter()
+                .map(|index| (index, &self.entities[index]))
+                .filter(|(_, entity)| entity.get_type() != projectile.shooter && entity.get_health().is_some() && !entity.is_dead())
+                .map(|(index, entity)| (index, entity.get_position(), entity.get_radius()));
+            let wall = self.map.wall_distance(start, direction, length);
+
+            match cast(start, direction, length, targets, wall) {
+                Some(hit) => {
+                    let kind = match hit.target {
+                        Some(target) => {
+                            let amount = projectile.shot.damage_at(projectile.travelled + hit.distance);
+                            let damage = Damage::new(amount, DamageKind::BULLET, Some(projectile.shot.origin));
+                            self.damage_events.push(DamageEvent { target, damage });
+                            ImpactKind::FLESH
+                        },
+                        None => ImpactKind::WALL,
+                    };
+
+                    self.impacts.push(Impact { position: hit.position, kind, ticks_left: IMPACT_TICKS });
+                    projectile.stop_at(hit.distance);
+                },
+                None => projectile.advance(length),
+            }
+
+            if projectile.is_hitscan() {
+                self.tracers.push(Tracer { from: start, to: projectile.position, ticks_left: TRACER_TICKS });
+            }
+        }
+        self.projectiles.retain(|projectile| !projectile.is_spent());
+
+        for impact in self.impacts.iter_mut() {
+            impact.ticks_left -= 1;
+        }
+        self.impacts.retain(|impact| impact.ticks_left > 0);
+
+        for tracer in self.tracers.iter_mut() {
+            tracer.ticks_left -= 1;
+        }
+        self.tracers.retain(|tracer| tracer.ticks_left > 0);
+    }
+
+    fn render_bullets(&self, window: &mut RenderWindow) {
+        for tracer in self.tracers.iter() {
+            let color = Color::rgba(255, 230, 150, (255 * tracer.ticks_left / TRACER_TICKS) as u8);
+            let line = [
+                Vertex::with_pos_color(tracer.from - self.camera_offset, color),
+                Vertex::with_pos_color(tracer.to - self.camera_offset, color),
+            ];
+            window.draw_primitives(&line, PrimitiveType::LINES, &RenderStates::default());
+        }
+
+        for projectile in self.projectiles.iter() {
+            let mut shape = CircleShape::new(2.0, 6);
+            shape.set_fill_color(Color::rgb(255, 230, 150));
+            shape.set_position(projectile.position - self.camera_offset - Vector2f::new(2.0, 2.0));
+            window.draw(&shape);
+        }
+
+        for impact in self.impacts.iter() {
+            let alpha = (255 * impact.ticks_left / IMPACT_TICKS) as u8;
+            let color = match impact.kind {
+                ImpactKind::FLESH => Color::rgba(150, 20, 20, alpha),
+                ImpactKind::WALL => Color::rgba(180, 170, 150, alpha),
+            };
+
+            let mut shape = CircleShape::new(3.0, 8);
+            shape.set_fill_color(color);
+            shape.set_position(impact.position - self.camera_offset - Vector2f::new(3.0, 3.0));
+            window.draw(&shape);
+        }
+    }
+
+    // swings from the player and zombies alike. everything caught takes damage at the end of
+    // the tick and gets shoved away straight away, but never through a wall
+    fn resolve_swings(&mut self, swings: Vec<Swing>) {
+        for swing in swings {
+            let targets = self.entities
+                .iter()
+                .enumerate()
+                .filter(|(_, entity)| entity.get_type() != swing.swinger && entity.get_health().is_some() && !entity.is_dead())
+                .filter(|(_, entity)| self.map.line_of_sight(swing.origin, entity.get_position()))
+                .map(|(index, entity)| (index, entity.get_position(), entity.get_radius()));
+
+            for hit in resolve_swing(&swing, targets) {
+                self.damage_events.push(DamageEvent { target: hit.target, damage: hit.damage });
+
+                let entity = &mut self.entities[hit.target];
+                let can_swim = entity.can_swim();
+                let position = move_and_slide(
+                    entity.get_position(),
+                    Vector2f::new(HITBOX_SIZE, HITBOX_SIZE),
+                    hit.push,
+                    |tile_x, tile_y| self.map.is_blocked(tile_x, tile_y, can_swim),
+                );
+                entity.set_position(position);
+            }
+        }
+    }
+
+    // dead zombies are swapped for corpses where they fell, a dead player ends the game
+    fn deal_damage(&mut self) {
+        for death in resolve_damage(&mut self.entities, &mut self.damage_events) {
+            match death.entity_type {
+                EntityType::ZOMBIE => self.entities[death.index] = Box::new(Corpse::new(self.assets.clone(), death.position)),
+                EntityType::PLAYER => self.player_death = Some(death.cause),
+                EntityType::CORPSE => {},
+            }
+        }
+    }
+
+    // drops zombies that wandered too far off and lets the director fill loaded chunks back up
+    fn populate(&mut self) {
+        let player_position = self.entities[self.player_index].get_position();
+        self.entities.retain(|entity| {
+            entity.get_type() == EntityType::PLAYER || !SpawnDirector::should_despawn(player_position, entity.get_position())
+        });
+
+        let loaded: Vec<((i32, i32), Biome)> = self.map.chunks
+            .iter()
+            .map(|(position, chunk)| (*position, chunk.biome))
+            .collect();
+        let zombies: Vec<Vector2f> = self.entities
+            .iter()
+            .filter(|entity| entity.get_type() == EntityType::ZOMBIE)
+            .map(|entity| entity.get_position())
+            .collect();
+
+        // a tile past the screen edge so nothing pops in half visible
+        let margin = TILE_SIZE as f32;
+        let view = FloatRect::new(
+            self.camera_offset.x - margin,
+            self.camera_offset.y - margin,
+            self.window_size.x + margin * 2.0,
+            self.window_size.y + margin * 2.0,
+        );
+
+        let map = &self.map;
+        let spawns = self.spawn_director.update(
+            &loaded,
+            &zombies,
+            |tile_x, tile_y| map.is_blocked(tile_x, tile_y, false),
+            |position| view.contains(position),
+        );
+
+        for spawn in spawns {
+            self.entities.push(Box::new(Zombie::new(self.assets.clone(), spawn.kind, spawn.position, spawn.seed)));
+        }
+
+        self.player_index = self.entities
+            .iter()
+            .position(|e| e.get_type() == EntityType::PLAYER)
+            .expect("Player does not exist!");
+    }
+
+    // opens or closes the nearest door next to the player, or fills up their water if there's
+    // no door but there is water
+    fn interact(&mut self) {
+        let (player_x, player_y) = world_to_tile(self.entities[self.player_index].get_position());
+
+        let mut neighbours = Vec::new();
+        for x in -1..=1 {
+            for y in -1..=1 {
+                // closing a door on yourself would leave you stuck inside it
+                if (x, y) != (0, 0) {
+                    neighbours.push((player_x + x, player_y + y));
+                }
+            }
+        }
+        neighbours.sort_by_key(|(x, y)| (x - player_x).abs() + (y - player_y).abs());
+
+        for (tile_x, tile_y) in neighbours.iter().copied() {
+            if self.map.toggle_door(tile_x, tile_y) {
+                let door = Vector2f::new((tile_x * TILE_SIZE + TILE_SIZE / 2) as f32, (tile_y * TILE_SIZE + TILE_SIZE / 2) as f32);
+                self.sounds.push(SoundEvent::new(door, DOOR_LOUDNESS));
+                return;
+            }
+        }
+
+        let by_water = self.map.is_water(player_x, player_y)
+            || neighbours.iter().any(|(tile_x, tile_y)| self.map.is_water(*tile_x, *tile_y));
+        if by_water {
+            self.entities[self.player_index].refill_water();
+        }
+    }
 }
 
 impl State for GameState {
@@ -85,6 +387,12 @@ impl State for GameState {
             Key::A => self.keyboard_data.a = true,
             Key::S => self.keyboard_data.s = true,
             Key::D => self.keyboard_data.d = true,
+            Key::LControl => self.keyboard_data.crouch = true,
+            Key::LShift => self.keyboard_data.sprint = true,
+            Key::R => self.keyboard_data.reload = true,
+            Key::F => self.keyboard_data.chamber = true,
+            Key::X => self.keyboard_data.unload = true,
+            Key::E => self.interact(),
             _ => {}
         }
     }
@@ -95,6 +403,8 @@ impl State for GameState {
             Key::A => self.keyboard_data.a = false,
             Key::S => self.keyboard_data.s = false,
             Key::D => self.keyboard_data.d = false,
+            Key::LControl => self.keyboard_data.crouch = false,
+            Key::LShift => self.keyboard_data.sprint = false,
             _ => {}
         }
     }
@@ -120,20 +430,91 @@ impl State for GameState {
     }
 
     fn update(&mut self) {
-        let entities = &mut self.entities;
-        let player_position = entities[self.player_index].get_position();
+        if self.player_death.is_some() {
+            return;
+        }
+        self.ticks += 1;
+
+        let player_position = self.entities[self.player_index].get_position();
 
         self.camera_offset = player_position - (self.window_size / 2.0);
+        self.mouse_data.world_position = self.mouse_data.position + self.camera_offset;
+
+        self.map.update(self.camera_offset, self.window_size);
+        // before anything moves, so the entity list stays put for the rest of the tick
+        self.populate();
+
+        let entities = &mut self.entities;
+
+        // built for things that can't swim, so it works for every zombie
+        let map = &self.map;
+        self.flow_field.update(player_position, |tile_x, tile_y| map.is_blocked(tile_x, tile_y, false));
 
         let reference_position = entities[self.player_index].get_position();
-        
-        for index in 0..entities.len() {
-            entities[index].update(
+        let light = self.map.light_level(reference_position);
+        let crouching = entities[self.player_index].is_crouching();
+
+        // sounds made last tick are heard this tick, everything made during this one waits
+        let sounds = std::mem::take(&mut self.sounds);
+        let mut swings = Vec::new();
+
+        for entity in entities.iter_mut() {
+            let previous_position = entity.get_position();
+            entity.set_flow_direction(self.flow_field.direction_at(previous_position));
+
+            // sight past this is never needed, so the ray isn't worth casting
+            let in_view = (previous_position - reference_position).length_sq() <= MAX_SIGHT_RANGE * MAX_SIGHT_RANGE;
+            entity.perceive(Perception {
+                player: reference_position,
+                line_of_sight: in_view && self.map.line_of_sight(previous_position, reference_position),
+                heard: loudest_heard(&sounds, previous_position, |from, to| self.map.walls_between(from, to)),
+                light,
+                crouching,
+            });
+
+            entity.update(
                 reference_position,
                 self.keyboard_data.clone(),
                 self.mouse_data.clone(),
             );
+            self.sounds.extend(entity.take_sounds());
+            let shooter = entity.get_type();
+            self.projectiles.extend(entity.take_shots().into_iter().map(|shot| Projectile::new(shot, shooter)));
+            swings.extend(entity.take_swing());
+
+            // entities move freely in their own update, the map then pushes the move back out of
+            // anything solid. the ground they start the frame on decides how far they get
+            let moved = (entity.get_position() - previous_position) * self.map.speed_multiplier(previous_position);
+            let can_swim = entity.can_swim();
+
+            let allowed = move_and_slide(
+                previous_position,
+                Vector2f::new(HITBOX_SIZE, HITBOX_SIZE),
+                moved,
+                |tile_x, tile_y| self.map.is_blocked(tile_x, tile_y, can_swim),
+            );
+            entity.set_position(allowed);
         }
+
+        self.keyboard_data.reload = false;
+        self.keyboard_data.chamber = false;
+        self.keyboard_data.unload = false;
+
+        self.resolve_swings(swings);
+
+        self.spatial_hash.rebuild(self.entities.iter().map(|entity| entity.get_position()));
+        self.step_projectiles();
+        self.deal_damage();
+        self.separate_entities();
+
+        self.path_planner.plan(&mut self.entities, &self.map);
+
+        self.map.update_roofs(self.entities[self.player_index].get_position());
+    }
+
+    fn next_state(&mut self) -> Option<Box<dyn State>> {
+        let cause = self.player_death?;
+        Some(Box::new(DeathState::new(self.assets.clone(), self.window_size, cause, self.ticks)))
     }
 
     fn render(&mut self, window: &mut RenderWindow) {
@@ -158,5 +539,9 @@ impl State for GameState {
                 self.camera_offset,
             );
         }
+
+        self.render_bullets(window);
+
+        self.map.render_roofs(window, self.camera_offset, self.window_size);
     }
 }
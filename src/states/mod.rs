@@ -1,4 +1,5 @@
 pub mod state;
 pub mod game_state;
+pub mod death_state;
 pub mod settings_state;
 pub mod menu_state;
\ No newline at end of file
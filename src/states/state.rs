@@ -10,5 +10,7 @@ pub trait State {
     fn mouse_position_event(&mut self, position: Vector2f);
 
     fn update(&mut self);
+    // the state to switch to, checked after every update
+    fn next_state(&mut self) -> Option<Box<dyn State>> { None }
     fn render(&mut self, window: &mut RenderWindow);
 }
@@ -0,0 +1,138 @@
+use std::{collections::HashMap, fs};
+
+use serde::Deserialize;
+use sfml::graphics::{Color, IntRect};
+
+use crate::map::{ASPHALT, DIRT, DOOR, DOOR_OPEN, FLOOR, GRASS, ROOF, SAND, STONE, WALL, WATER};
+
+pub const TILE_REGISTRY_PATH: &str = "res/tiles.toml";
+
+// tiles the world generator refers to by ID, they have to be at these positions in the file
+const BUILTIN_TILES: [(&str, u16); 11] = [
+    ("grass", GRASS),
+    ("sand", SAND),
+    ("dirt", DIRT),
+    ("stone", STONE),
+    ("asphalt", ASPHALT),
+    ("floor", FLOOR),
+    ("wall", WALL),
+    ("door", DOOR),
+    ("door_open", DOOR_OPEN),
+    ("roof", ROOF),
+    ("water", WATER),
+];
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct TileDefinition {
+    pub name: String,
+    // left, top, width, height on the terrain texture
+    pub atlas: [i32; 4],
+    // multiplied with the texture, lets one atlas rect serve several tiles
+    #[serde(default = "default_tint")]
+    pub tint: [u8; 3],
+    #[serde(default = "default_walkable")]
+    pub walkable: bool,
+    #[serde(default)]
+    pub blocks_sight: bool,
+    // only entities that can swim get across
+    #[serde(default)]
+    pub swim: bool,
+    #[serde(default = "default_multiplier")]
+    pub speed_multiplier: f32,
+    #[serde(default)]
+    pub footstep_sound: Option<String>,
+    #[serde(default = "default_multiplier")]
+    pub spawn_weight: f32,
+}
+
+impl TileDefinition {
+    pub fn texture_rect(&self) -> IntRect {
+        IntRect::new(self.atlas[0], self.atlas[1], self.atlas[2], self.atlas[3])
+    }
+
+    pub fn color(&self) -> Color {
+        Color::rgb(self.tint[0], self.tint[1], self.tint[2])
+    }
+}
+
+fn default_tint() -> [u8; 3] { [255, 255, 255] }
+fn default_walkable() -> bool { true }
+fn default_multiplier() -> f32 { 1.0 }
+
+#[derive(Deserialize)]
+struct TileFile {
+    tile: Vec<TileDefinition>,
+}
+
+// every tile the game knows about. tile IDs stored in chunks index into this
+pub struct TileRegistry {
+    tiles: Vec<TileDefinition>,
+    ids: HashMap<String, u16>,
+}
+
+impl TileRegistry {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+        TileRegistry::from_toml(&source).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        let file: TileFile = toml::from_str(source).map_err(|error| error.to_string())?;
+
+        let mut ids = HashMap::new();
+        for (id, tile) in file.tile.iter().enumerate() {
+            if ids.insert(tile.name.clone(), id as u16).is_some() {
+                return Err(format!("tile \"{}\" is defined twice", tile.name));
+            }
+        }
+
+        for (name, id) in BUILTIN_TILES {
+            if ids.get(name) != Some(&id) {
+                return Err(format!("tile \"{}\" has to be entry {}", name, id));
+            }
+        }
+
+        Ok(TileRegistry { tiles: file.tile, ids })
+    }
+
+    // unknown IDs (a save made with a different tile file, say) fall back to the first tile
+    pub fn get(&self, id: u16) -> &TileDefinition {
+        self.tiles.get(id as usize).unwrap_or(&self.tiles[0])
+    }
+
+    pub fn id(&self, name: &str) -> Option<u16> {
+        self.ids.get(name).copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shipped_tiles_load() {
+        let registry = TileRegistry::from_toml(include_str!("../res/tiles.toml")).unwrap();
+
+        assert_eq!(registry.id("stone"), Some(STONE));
+        assert_eq!(registry.get(SAND).texture_rect(), IntRect::new(32, 0, 32, 32));
+        assert!(registry.get(GRASS).walkable);
+        assert!(!registry.get(WALL).walkable && registry.get(WALL).blocks_sight);
+        assert!(registry.get(DOOR_OPEN).walkable && !registry.get(DOOR_OPEN).blocks_sight);
+        assert!(registry.get(WATER).swim && registry.get(WATER).speed_multiplier < 1.0);
+    }
+
+    #[test]
+    fn builtin_tiles_must_keep_their_ids() {
+        let source = r#"
+            [[tile]]
+            name = "sand"
+            atlas = [32, 0, 32, 32]
+
+            [[tile]]
+            name = "grass"
+            atlas = [0, 0, 32, 32]
+        "#;
+
+        assert!(TileRegistry::from_toml(source).is_err());
+    }
+}
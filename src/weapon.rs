@@ -0,0 +1,414 @@
+use std::{collections::HashMap, fs};
+
+use rand::Rng;
+use serde::Deserialize;
+use sfml::system::Vector2f;
+
+use crate::{
+    ballistics::{spread_direction, Shot},
+    sound::GUNSHOT_LOUDNESS,
+};
+
+pub const WEAPON_REGISTRY_PATH: &str = "res/weapons.toml";
+
+// ticks are 10 ms
+const TICKS_PER_MINUTE: f32 = 6000.0;
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum FireMode {
+    SEMI = 0,
+    AUTO = 1,
+    BURST = 2,
+}
+
+#[derive(Deserialize, Clone, Debug)]
+pub struct WeaponDefinition {
+    pub name: String,
+    pub fire_mode: FireMode,
+    #[serde(default = "default_burst_count")]
+    pub burst_count: u32,
+    // rounds per minute
+    pub fire_rate: f32,
+    pub magazine: u32,
+    #[serde(default)]
+    pub reserve: u32,
+    pub reload_ticks: u32,
+    pub chamber_ticks: u32,
+    pub damage: f32,
+    #[serde(default = "default_pellets")]
+    pub pellets: u32,
+    // degrees
+    pub spread: f32,
+    pub range: f32,
+    pub falloff_start: f32,
+    // None for hitscan
+    #[serde(default)]
+    pub projectile_speed: Option<f32>,
+    #[serde(default = "default_loudness")]
+    pub loudness: f32,
+    #[serde(default)]
+    pub fire_sound: Option<String>,
+    #[serde(default)]
+    pub reload_sound: Option<String>,
+    #[serde(default)]
+    pub chamber_sound: Option<String>,
+    #[serde(default)]
+    pub unload_sound: Option<String>,
+}
+
+impl WeaponDefinition {
+    pub fn ticks_between_shots(&self) -> u32 {
+        (TICKS_PER_MINUTE / self.fire_rate).round().max(1.0) as u32
+    }
+
+    // names of every sound it uses
+    pub fn sounds(&self) -> impl Iterator<Item = &str> {
+        [&self.fire_sound, &self.reload_sound, &self.chamber_sound, &self.unload_sound]
+            .into_iter()
+            .flatten()
+            .map(|sound| sound.as_str())
+    }
+}
+
+fn default_burst_count() -> u32 { 3 }
+fn default_pellets() -> u32 { 1 }
+fn default_loudness() -> f32 { GUNSHOT_LOUDNESS }
+
+#[derive(Deserialize)]
+struct WeaponFile {
+    weapon: Vec<WeaponDefinition>,
+}
+
+// every weapon the game knows about, by name
+pub struct WeaponRegistry {
+    weapons: HashMap<String, WeaponDefinition>,
+}
+
+impl WeaponRegistry {
+    pub fn load(path: &str) -> Result<Self, String> {
+        let source = fs::read_to_string(path).map_err(|error| format!("{}: {}", path, error))?;
+        WeaponRegistry::from_toml(&source).map_err(|error| format!("{}: {}", path, error))
+    }
+
+    pub fn from_toml(source: &str) -> Result<Self, String> {
+        let file: WeaponFile = toml::from_str(source).map_err(|error| error.to_string())?;
+
+        let mut weapons = HashMap::new();
+        for weapon in file.weapon {
+            if weapon.magazine == 0 || weapon.fire_rate <= 0.0 || weapon.pellets == 0 {
+                return Err(format!("weapon \"{}\" needs a magazine, a fire rate and at least one pellet", weapon.name));
+            }
+            if let Some(existing) = weapons.insert(weapon.name.clone(), weapon) {
+                return Err(format!("weapon \"{}\" is defined twice", existing.name));
+            }
+        }
+
+        Ok(WeaponRegistry { weapons })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&WeaponDefinition> {
+        self.weapons.get(name)
+    }
+
+    pub fn definitions(&self) -> impl Iterator<Item = &WeaponDefinition> {
+        self.weapons.values()
+    }
+}
+
+// things a weapon did this tick that the holder should react to, mostly with sounds
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WeaponEvent {
+    FIRED = 0,
+    RELOADING = 1,
+    CHAMBERING = 2,
+    UNLOADED = 3,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WeaponAction {
+    RELOADING = 0,
+    CHAMBERING = 1,
+}
+
+// a gun someone is holding. the chambered round is the only one that can be fired, every
+// shot pulls the next one up from the magazine
+#[derive(Clone, Debug)]
+pub struct Weapon {
+    pub definition: WeaponDefinition,
+    pub magazine: u32,
+    pub chambered: bool,
+    pub reserve: u32,
+    // the action in progress and the ticks it has left
+    pub action: Option<(WeaponAction, u32)>,
+    cooldown: u32,
+    // shots left in the current trigger pull
+    queued: u32,
+    trigger_was_held: bool,
+}
+
+impl Weapon {
+    // full magazine and a round in the chamber
+    pub fn new(definition: WeaponDefinition) -> Self {
+        Weapon {
+            magazine: definition.magazine,
+            chambered: true,
+            reserve: definition.reserve,
+            action: None,
+            cooldown: 0,
+            queued: 0,
+            trigger_was_held: false,
+            definition,
+        }
+    }
+
+    pub fn rounds(&self) -> u32 {
+        self.magazine + u32::from(self.chambered)
+    }
+
+    pub fn sound(&self, event: WeaponEvent) -> Option<&str> {
+        match event {
+            WeaponEvent::FIRED => self.definition.fire_sound.as_deref(),
+            WeaponEvent::RELOADING => self.definition.reload_sound.as_deref(),
+            WeaponEvent::CHAMBERING => self.definition.chamber_sound.as_deref(),
+            WeaponEvent::UNLOADED => self.definition.unload_sound.as_deref(),
+        }
+    }
+
+    // swaps in a fresh magazine from the reserve, then racks a round if the chamber is empty
+    pub fn reload(&mut self) -> Option<WeaponEvent> {
+        if self.action.is_some() || self.magazine >= self.definition.magazine || self.reserve == 0 {
+            return None;
+        }
+
+        self.action = Some((WeaponAction::RELOADING, self.definition.reload_ticks));
+        Some(WeaponEvent::RELOADING)
+    }
+
+    // racks the next round into the chamber. whatever was in there already is thrown out
+    pub fn chamber(&mut self) -> Option<WeaponEvent> {
+        if self.action.is_some() || (self.magazine == 0 && !self.chambered) {
+            return None;
+        }
+
+        self.action = Some((WeaponAction::CHAMBERING, self.definition.chamber_ticks));
+        Some(WeaponEvent::CHAMBERING)
+    }
+
+    // every round in the gun goes back to the reserve
+    pub fn unload(&mut self) -> Option<WeaponEvent> {
+        if self.action.is_some() || self.rounds() == 0 {
+            return None;
+        }
+
+        self.reserve += self.rounds();
+        self.magazine = 0;
+        self.chambered = false;
+        Some(WeaponEvent::UNLOADED)
+    }
+
+    fn finish(&mut self, action: WeaponAction) -> Option<WeaponEvent> {
+        match action {
+            WeaponAction::RELOADING => {
+                let loaded = (self.definition.magazine - self.magazine).min(self.reserve);
+                self.magazine += loaded;
+                self.reserve -= loaded;
+
+                if self.chambered {
+                    None
+                } else {
+                    self.chamber()
+                }
+            },
+            WeaponAction::CHAMBERING => {
+                self.chambered = self.magazine > 0;
+                self.magazine = self.magazine.saturating_sub(1);
+                None
+            },
+        }
+    }
+
+    // once per tick with whether the trigger is held down
+    pub fn update(&mut self, trigger: bool) -> Vec<WeaponEvent> {
+        let mut events = Vec::new();
+        self.cooldown = self.cooldown.saturating_sub(1);
+
+        if let Some((action, ticks_left)) = self.action {
+            if ticks_left <= 1 {
+                self.action = None;
+                events.extend(self.finish(action));
+            } else {
+                self.action = Some((action, ticks_left - 1));
+            }
+        }
+
+        let ready = self.action.is_none() && self.cooldown == 0 && self.chambered;
+
+        let pulled = trigger && !self.trigger_was_held;
+        self.trigger_was_held = trigger;
+        if pulled {
+            self.queued = match self.definition.fire_mode {
+                // clicks while it isn't ready are lost, like on a real trigger
+                FireMode::SEMI => u32::from(ready),
+                FireMode::BURST => self.definition.burst_count,
+                FireMode::AUTO => 0,
+            };
+        }
+
+        let wants_to_fire = match self.definition.fire_mode {
+            FireMode::AUTO => trigger,
+            FireMode::SEMI | FireMode::BURST => self.queued > 0,
+        };
+        if !wants_to_fire {
+            return events;
+        }
+
+        // out of rounds ends the burst, nothing happens until it's reloaded or racked
+        if !self.chambered {
+            self.queued = 0;
+            return events;
+        }
+        if !ready {
+            return events;
+        }
+
+        // self loading, the recoil brings the next round up
+        self.chambered = self.magazine > 0;
+        self.magazine = self.magazine.saturating_sub(1);
+        self.cooldown = self.definition.ticks_between_shots();
+        self.queued = self.queued.saturating_sub(1);
+        events.push(WeaponEvent::FIRED);
+
+        events
+    }
+
+    // one shot per pellet, each with its own spread
+    pub fn shots(&self, origin: Vector2f, aim: Vector2f, rng: &mut impl Rng) -> Vec<Shot> {
+        (0..self.definition.pellets)
+            .map(|_| Shot {
+                origin,
+                direction: spread_direction(aim, self.definition.spread, rng),
+                range: self.definition.range,
+                damage: self.definition.damage,
+                falloff_start: self.definition.falloff_start,
+                speed: self.definition.projectile_speed,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{rngs::StdRng, SeedableRng};
+
+    use super::*;
+
+    fn registry() -> WeaponRegistry {
+        WeaponRegistry::from_toml(include_str!("../res/weapons.toml")).unwrap()
+    }
+
+    fn weapon(name: &str) -> Weapon {
+        Weapon::new(registry().get(name).unwrap().clone())
+    }
+
+    // holds or taps the trigger for a number of ticks, counting shots
+    fn fire_for(weapon: &mut Weapon, ticks: u32, trigger: impl Fn(u32) -> bool) -> usize {
+        (0..ticks)
+            .map(|tick| weapon.update(trigger(tick)))
+            .filter(|events| events.contains(&WeaponEvent::FIRED))
+            .count()
+    }
+
+    #[test]
+    fn shipped_weapons_load() {
+        let registry = registry();
+
+        let handgun = registry.get("handgun").unwrap();
+        assert_eq!(handgun.fire_mode, FireMode::SEMI);
+        assert_eq!(handgun.ticks_between_shots(), 20);
+        assert_eq!(handgun.chamber_sound.as_deref(), Some("handgun_cock"));
+
+        assert_eq!(registry.get("rifle").unwrap().fire_mode, FireMode::AUTO);
+        assert!(registry.get("shotgun").unwrap().pellets > 1);
+        assert!(registry.get("bazooka").is_none());
+
+        let duplicate = include_str!("../res/weapons.toml").to_string() + "\n[[weapon]]\nname = \"handgun\"\nfire_mode = \"auto\"\nfire_rate = 1\nmagazine = 1\nreload_ticks = 1\nchamber_ticks = 1\ndamage = 1\nspread = 0\nrange = 1\nfalloff_start = 1\n";
+        assert!(WeaponRegistry::from_toml(&duplicate).is_err());
+    }
+
+    #[test]
+    fn semi_auto_fires_once_per_pull_and_runs_dry() {
+        let mut handgun = weapon("handgun");
+
+        // held down the whole time, still just one shot
+        assert_eq!(fire_for(&mut handgun, 100, |_| true), 1);
+        assert_eq!(handgun.rounds(), 12);
+
+        // a pull every other tick is faster than it cycles, the early ones are lost
+        let shots = fire_for(&mut handgun, 100, |tick| tick % 2 == 0);
+        assert!(shots > 1 && shots < 50);
+
+        let mut handgun = weapon("handgun");
+        assert_eq!(fire_for(&mut handgun, 2000, |tick| tick % 40 == 0), 13);
+        assert_eq!((handgun.magazine, handgun.chambered), (0, false));
+    }
+
+    #[test]
+    fn automatic_and_burst_fire_keep_their_rate() {
+        let mut rifle = weapon("rifle");
+        // 600 rounds per minute is one every 10 ticks
+        assert_eq!(fire_for(&mut rifle, 100, |_| true), 10);
+
+        let mut burst = weapon("rifle");
+        burst.definition.fire_mode = FireMode::BURST;
+        assert_eq!(fire_for(&mut burst, 200, |tick| tick == 0), 3);
+    }
+
+    #[test]
+    fn reloading_refills_and_racks_an_empty_chamber() {
+        let mut handgun = weapon("handgun");
+        assert_eq!(handgun.reload(), None);
+
+        fire_for(&mut handgun, 2000, |tick| tick % 40 == 0);
+        assert_eq!(handgun.reload(), Some(WeaponEvent::RELOADING));
+        assert_eq!(handgun.reload(), None);
+
+        // can't fire mid reload, then the slide has to go forward before it's ready
+        let mut events = Vec::new();
+        for _ in 0..handgun.definition.reload_ticks {
+            events.extend(handgun.update(true));
+        }
+        assert_eq!(events, vec![WeaponEvent::CHAMBERING]);
+        assert_eq!((handgun.magazine, handgun.reserve), (12, 12));
+
+        for _ in 0..handgun.definition.chamber_ticks {
+            handgun.update(false);
+        }
+        assert_eq!((handgun.magazine, handgun.chambered), (11, true));
+        assert_eq!(handgun.sound(WeaponEvent::RELOADING), Some("handgun_release"));
+    }
+
+    #[test]
+    fn racking_throws_out_the_chambered_round_and_unloading_keeps_it() {
+        let mut handgun = weapon("handgun");
+        assert_eq!(handgun.chamber(), Some(WeaponEvent::CHAMBERING));
+        for _ in 0..handgun.definition.chamber_ticks {
+            handgun.update(false);
+        }
+        assert_eq!((handgun.magazine, handgun.chambered), (11, true));
+
+        assert_eq!(handgun.unload(), Some(WeaponEvent::UNLOADED));
+        assert_eq!((handgun.rounds(), handgun.reserve), (0, 36));
+        assert_eq!(handgun.chamber(), None);
+        assert_eq!(fire_for(&mut handgun, 10, |_| true), 0);
+    }
+
+    #[test]
+    fn shotguns_fire_every_pellet() {
+        let shotgun = weapon("shotgun");
+        let shots = shotgun.shots(Vector2f::new(0.0, 0.0), Vector2f::new(1.0, 0.0), &mut StdRng::seed_from_u64(1));
+
+        assert_eq!(shots.len(), 8);
+        assert!(shots.iter().all(|shot| shot.speed.is_none() && shot.damage == 12.0));
+    }
+}